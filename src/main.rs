@@ -38,18 +38,245 @@
 // --- END OF STARTING INSTRUCTIONS ---
 
 
-use std::io::{self, Write}; // Import necessary modules for input/output
+use std::collections::BTreeMap; // Sorted maps for per-subject aggregation
+use std::env; // Access to command-line arguments
+use std::fs::{self, File, OpenOptions}; // Reading and writing files, including appending
+use std::io::{self, BufRead, BufReader, BufWriter, IsTerminal, Write}; // Import necessary modules for input/output
+use std::path::{Path, PathBuf}; // Looking at file extensions and finding the autosave
+use std::process; // Exiting with a status code on errors
+use std::sync::OnceLock; // Program-wide settings fixed once at startup, such as the no-grade label
+use std::sync::atomic::{AtomicBool, Ordering}; // Program-wide switches such as --force-color and --ignore-zeros
 
 /// ---
 /// ### 1. Define Student Struct
 /// This struct will hold the data for each student.
 /// It encapsulates the student's name, their total marks across all subjects,
 /// and the total number of subjects they took.
+/// When assessment weights are configured, the marks are split into an
+/// internal assessment component and a final exam component.
+/// When marks are entered per subject, the individual subjects are kept too and
+/// the total marks and subject count are derived from them.
+/// Names aren't unique, so each student also has an ID (such as a roll number)
+/// that identifies them when looking students up.
 /// ---
+#[derive(Clone)]
 struct Student {
+    student_id: String,        // Roll number or other unique ID (empty when the source has none)
+    section: Option<String>,   // Class section or group, e.g. "7B", for per-section summaries
     name: String,
     total_marks: u32,
     num_subjects: u32,
+    internal: u32,             // Internal assessment marks (only used with weights)
+    exam: u32,                 // Final exam marks (only used with weights)
+    weights: Option<Weights>,  // How internal and exam marks combine, if configured
+    subjects: Vec<Subject>,    // Individual subject marks (empty when only a total was given)
+    override_grade: Option<Grade>, // A grade set by hand that replaces the computed one
+    incomplete: bool,          // The course isn't finished: graded "I" and left out of class averages
+}
+
+/// A single subject and the mark the student obtained in it.
+#[derive(Clone)]
+struct Subject {
+    name: String,
+    mark: Mark,
+    components: Vec<Component>, // Weighted parts the mark was computed from (empty when it was entered directly)
+}
+
+/// A subject's mark, or a note that the student was absent. An absent subject
+/// isn't a zero: it is left out of the average altogether. Absent orders below
+/// any score, so the better of two marks is simply the larger.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+enum Mark {
+    Absent,
+    Score(u32),
+}
+
+impl Mark {
+    /// The score, or None when the student was absent.
+    fn score(self) -> Option<u32> {
+        match self {
+            Mark::Score(score) => Some(score),
+            Mark::Absent => None,
+        }
+    }
+
+    /// True for "AB" or "absent" (ignoring case), how an absence is typed.
+    fn is_absent_text(text: &str) -> bool {
+        let text = text.trim();
+        text.eq_ignore_ascii_case("AB") || text.eq_ignore_ascii_case("absent")
+    }
+}
+
+impl std::fmt::Display for Mark {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Mark::Score(score) => write!(f, "{}", score),
+            Mark::Absent => write!(f, "Absent"),
+        }
+    }
+}
+
+/// One weighted part of a subject's mark, such as homework, quizzes or the exam.
+/// For example an exam worth 60% that scored 72 is
+/// `Component { name: "Exam".to_string(), score: 72.0, weight: 0.6 }`.
+#[derive(Clone)]
+struct Component {
+    name: String,
+    score: f64,  // Out of MAX_SUBJECT_MARK, like a subject mark
+    weight: f64, // Share of the subject mark; a subject's weights sum to 1.0
+}
+
+/// Returned when a subject's component weights don't add up to 1.0.
+#[derive(Debug)]
+struct ComponentWeights {
+    subject: String,
+    sum: f64,
+}
+
+impl std::fmt::Display for ComponentWeights {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "component weights for '{}' sum to {}, not 1.0", self.subject, self.sum)
+    }
+}
+
+impl Subject {
+    /// Builds a subject whose mark is the weighted sum of `components`, rounded
+    /// to a whole mark. Fails unless the weights sum to 1.0.
+    fn with_components(name: String, components: Vec<Component>) -> Result<Self, ComponentWeights> {
+        let sum: f64 = components.iter().map(|component| component.weight).sum();
+        if (sum - 1.0).abs() > 1e-9 {
+            return Err(ComponentWeights { subject: name, sum });
+        }
+        let mut subject = Subject { name, mark: Mark::Score(0), components };
+        subject.mark = Mark::Score(subject.weighted_subject_mark().round() as u32);
+        Ok(subject)
+    }
+
+    /// The subject mark before rounding: the weighted sum of the component
+    /// scores, or just the mark when there are no components (0 when absent).
+    fn weighted_subject_mark(&self) -> f64 {
+        if self.components.is_empty() {
+            return self.mark.score().unwrap_or(0) as f64;
+        }
+        self.components.iter().map(|component| component.score * component.weight).sum()
+    }
+}
+
+/// Returned when a subject name doesn't match any of the student's subjects.
+#[derive(Debug)]
+struct SubjectNotFound(String);
+
+impl std::fmt::Display for SubjectNotFound {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "no subject named '{}'", self.0)
+    }
+}
+
+/// Returned when a student's stated total and subject count disagree with
+/// their per-subject marks.
+#[derive(Debug)]
+struct TotalMismatch {
+    stated_total: u32,
+    stated_subjects: u32,
+    subject_total: u32,    // The per-subject marks added up
+    subject_count: u32,    // How many subjects have marks
+}
+
+impl std::fmt::Display for TotalMismatch {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(
+            f,
+            "stated total {} over {} subjects, but the subject marks add up to {} over {}",
+            self.stated_total, self.stated_subjects, self.subject_total, self.subject_count
+        )
+    }
+}
+
+/// Returned when a student's stated subject count differs from the number of
+/// subjects listed, which would divide their average by the wrong count.
+#[derive(Debug)]
+struct SubjectCountMismatch {
+    stated: u32, // num_subjects as given
+    actual: u32, // How many subjects are listed
+}
+
+impl std::fmt::Display for SubjectCountMismatch {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "num_subjects is {}, but {} subjects are listed with marks", self.stated, self.actual)
+    }
+}
+
+/// Returned when a student has the same subject twice and the policy rejects it.
+#[derive(Debug)]
+struct DuplicateSubject(String);
+
+impl std::fmt::Display for DuplicateSubject {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "subject '{}' appears more than once", self.0)
+    }
+}
+
+/// What to do when a student has a subject (ignoring case) more than once,
+/// which would otherwise count it twice in the average.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+enum DuplicateSubjects {
+    #[default]
+    Latest,  // Keep one entry with the mark given last, as a correction would be
+    Highest, // Keep one entry with the best mark, as for a retake
+    Reject,  // Treat it as a mistake to fix
+}
+
+impl DuplicateSubjects {
+    /// Parses a --duplicate-subjects value: `latest`, `highest` or `reject`.
+    fn parse(value: &str) -> Option<Self> {
+        match value {
+            "latest" => Some(DuplicateSubjects::Latest),
+            "highest" => Some(DuplicateSubjects::Highest),
+            "reject" => Some(DuplicateSubjects::Reject),
+            _ => None,
+        }
+    }
+}
+
+/// Why two student records could not be merged.
+#[derive(Debug)]
+enum MergeError {
+    DifferentIds { ours: String, theirs: String }, // The records belong to different students
+    MixedMarks, // One record has per-subject marks and the other only a total
+}
+
+impl std::fmt::Display for MergeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            MergeError::DifferentIds { ours, theirs } => {
+                write!(f, "student IDs differ ('{}' and '{}')", ours, theirs)
+            }
+            MergeError::MixedMarks => write!(f, "one record has subject marks and the other only a total"),
+        }
+    }
+}
+
+/// A difference between the subjects a student took and the expected curriculum.
+enum CurriculumIssue {
+    Missing(String), // An expected subject the student has no mark for
+    Extra(String),   // A subject the student took that isn't in the curriculum
+}
+
+impl std::fmt::Display for CurriculumIssue {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            CurriculumIssue::Missing(name) => write!(f, "missing {}", name),
+            CurriculumIssue::Extra(name) => write!(f, "extra {}", name),
+        }
+    }
+}
+
+/// The share of the final mark contributed by each assessment component.
+/// For example 30% internal and 70% exam is `Weights { internal: 0.3, exam: 0.7 }`.
+#[derive(Clone, Copy)]
+struct Weights {
+    internal: f64,
+    exam: f64,
 }
 
 /// ---
@@ -58,14 +285,38 @@ struct Student {
 /// This makes our code more readable and prevents invalid grade assignments.
 /// Invalid is included for edge cases, like a student having zero subjects.
 /// ---
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 enum Grade {
     A, // 90+
     B, // 75-89
     C, // 60-74
     D, // Below 60
+    Incomplete, // The course isn't finished yet, whatever the marks so far
     Invalid, // Grade cannot be determined (e.g., num_subjects = 0)
 }
 
+/// Grades compare by merit, so a better grade is greater: Invalid and
+/// Incomplete come lowest, then D, C, B and finally A.
+impl Ord for Grade {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        let merit = |grade: &Grade| match grade {
+            Grade::Invalid => 0,
+            Grade::Incomplete => 1,
+            Grade::D => 2,
+            Grade::C => 3,
+            Grade::B => 4,
+            Grade::A => 5,
+        };
+        merit(self).cmp(&merit(other))
+    }
+}
+
+impl PartialOrd for Grade {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
 impl Grade {
     /// A simple method to convert the Grade enum variant into a user-friendly string.
     /// This is useful when displaying the grade in the report card.
@@ -75,11 +326,252 @@ impl Grade {
             Grade::B => "B",
             Grade::C => "C",
             Grade::D => "D",
-            Grade::Invalid => "N/A", // Not Applicable
+            Grade::Incomplete => "I",
+            Grade::Invalid => no_grade_label(), // "N/A" (Not Applicable) unless the config says otherwise
+        }
+    }
+
+    /// The grade's slot in a tally such as ClassSummary::grade_counts: A is 0
+    /// through D at 3, then Invalid at 4. Incomplete comes after the tally's
+    /// GRADE_SLOTS, since those students are counted separately.
+    fn index(&self) -> usize {
+        match self {
+            Grade::A => 0,
+            Grade::B => 1,
+            Grade::C => 2,
+            Grade::D => 3,
+            Grade::Invalid => 4,
+            Grade::Incomplete => 5,
+        }
+    }
+
+    /// The grade in the given slot (see index), or None past the last grade.
+    fn from_index(index: usize) -> Option<Grade> {
+        match index {
+            0 => Some(Grade::A),
+            1 => Some(Grade::B),
+            2 => Some(Grade::C),
+            3 => Some(Grade::D),
+            4 => Some(Grade::Invalid),
+            5 => Some(Grade::Incomplete),
+            _ => None,
+        }
+    }
+
+    /// Raises a letter grade to at least `min` and lowers it to at most `max`,
+    /// for --min-grade and --max-grade. Incomplete and Invalid have no band to
+    /// move within and are returned unchanged. `min` must not be above `max`.
+    fn clamp(self, min: Grade, max: Grade) -> Grade {
+        match self {
+            Grade::Incomplete | Grade::Invalid => self,
+            _ => Ord::clamp(self, min, max),
+        }
+    }
+
+    /// True for the passing grades A, B and C.
+    fn is_passing(&self) -> bool {
+        matches!(self, Grade::A | Grade::B | Grade::C)
+    }
+
+    /// True for a distinction, which is an A.
+    fn is_distinction(&self) -> bool {
+        matches!(self, Grade::A)
+    }
+
+    /// True for the failing grade D. Incomplete and Invalid are neither passing nor failing.
+    fn is_failing(&self) -> bool {
+        matches!(self, Grade::D)
+    }
+
+    /// The next grade up, or None for an A (and for Invalid, which has no band).
+    fn next_up(&self) -> Option<Grade> {
+        match self {
+            Grade::D => Some(Grade::C),
+            Grade::C => Some(Grade::B),
+            Grade::B => Some(Grade::A),
+            Grade::A | Grade::Incomplete | Grade::Invalid => None,
+        }
+    }
+
+    /// Grade points on the usual 4-point scale (A = 4 down to D = 1), or None
+    /// for Incomplete and Invalid, which carry no points.
+    fn points(&self) -> Option<f64> {
+        match self {
+            Grade::A => Some(MAX_GRADE_POINTS),
+            Grade::B => Some(3.0),
+            Grade::C => Some(2.0),
+            Grade::D => Some(1.0),
+            Grade::Incomplete | Grade::Invalid => None,
+        }
+    }
+
+    /// Parses a letter grade typed by the user ("a" to "d", case-insensitive).
+    fn from_letter(letter: &str) -> Option<Grade> {
+        match letter.trim().to_ascii_uppercase().as_str() {
+            "A" => Some(Grade::A),
+            "B" => Some(Grade::B),
+            "C" => Some(Grade::C),
+            "D" => Some(Grade::D),
+            _ => None,
+        }
+    }
+}
+
+/// Set from the config's `no_grade_label`: what Grade::Invalid is shown as.
+static NO_GRADE_LABEL: OnceLock<String> = OnceLock::new();
+
+/// The text shown for a student who can't be graded: "N/A" by default, or the
+/// config's `no_grade_label` (e.g. "Ungraded").
+fn no_grade_label() -> &'static str {
+    NO_GRADE_LABEL.get().map(String::as_str).unwrap_or("N/A")
+}
+
+/// The minimum average needed for each grade. Anything below the C cutoff is a D.
+#[derive(Clone, Copy)]
+struct GradingScale {
+    a: f64, // 90 by default
+    b: f64, // 75 by default
+    c: f64, // 60 by default
+}
+
+impl Default for GradingScale {
+    fn default() -> Self {
+        Self { a: 90.0, b: 75.0, c: 60.0 }
+    }
+}
+
+/// Set from a CSV file's `# scale:` comment or by --custom-scale: the scale
+/// every grade is worked out with.
+static GRADING_SCALE: OnceLock<GradingScale> = OnceLock::new();
+
+/// The grading scale in use: the input file's or the user's, or the default 90/75/60.
+fn grading_scale() -> GradingScale {
+    GRADING_SCALE.get().copied().unwrap_or_default()
+}
+
+/// Why a set of cutoffs doesn't make a usable grading scale.
+#[derive(Debug)]
+enum ScaleError {
+    Malformed, // Not three numbers separated by commas
+    OutOfRange { grade: &'static str, cutoff: f64 }, // A cutoff below 0 or above 100
+    // A lower grade's cutoff that isn't below the higher grade's, each as (grade, cutoff)
+    NotDescending { higher: (&'static str, f64), lower: (&'static str, f64) },
+}
+
+impl std::fmt::Display for ScaleError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            ScaleError::Malformed => write!(f, "expected A, B and C cutoffs from high to low, e.g. 85,70,55"),
+            ScaleError::OutOfRange { grade, cutoff } => {
+                write!(f, "the {} cutoff {} is not between 0 and 100", grade, cutoff)
+            }
+            ScaleError::NotDescending { higher, lower } => {
+                write!(f, "the {} cutoff ({}) must be below the {} cutoff ({})", lower.0, lower.1, higher.0, higher.1)
+            }
+        }
+    }
+}
+
+/// Checks that the `lower` grade's cutoff is strictly below the `higher` one's.
+fn check_descending(higher: (&'static str, f64), lower: (&'static str, f64)) -> Result<(), ScaleError> {
+    if lower.1 < higher.1 { Ok(()) } else { Err(ScaleError::NotDescending { higher, lower }) }
+}
+
+impl GradingScale {
+    /// Builds a scale from its cutoffs, if they pass validate.
+    fn new(a: f64, b: f64, c: f64) -> Result<Self, ScaleError> {
+        let scale = Self { a, b, c };
+        scale.validate()?;
+        Ok(scale)
+    }
+
+    /// Checks that every cutoff lies from 0 to 100 and that they fall strictly
+    /// from A to C. D has no cutoff of its own: it takes everything below C, so
+    /// a scale that passes gives every average from 0 to 100 exactly one grade.
+    fn validate(&self) -> Result<(), ScaleError> {
+        let cutoffs = [("A", self.a), ("B", self.b), ("C", self.c)];
+        for (grade, cutoff) in cutoffs {
+            if !(0.0..=100.0).contains(&cutoff) {
+                return Err(ScaleError::OutOfRange { grade, cutoff });
+            }
+        }
+        for pair in cutoffs.windows(2) {
+            check_descending(pair[0], pair[1])?;
+        }
+        Ok(())
+    }
+
+    /// The averages a letter grade's band covers, e.g. "75 to below 90", or
+    /// None for Incomplete and Invalid, which have no band.
+    fn band(&self, grade: Grade) -> Option<String> {
+        match grade {
+            Grade::A => Some(format!("{} or more", self.a)),
+            Grade::B => Some(format!("{} to below {}", self.b, self.a)),
+            Grade::C => Some(format!("{} to below {}", self.c, self.b)),
+            Grade::D => Some(format!("below {}", self.c)),
+            Grade::Incomplete | Grade::Invalid => None,
+        }
+    }
+
+    /// Looks up the grade band an average falls into.
+    fn grade_for(&self, average: f64) -> Grade {
+        if average >= self.a {
+            Grade::A
+        } else if average >= self.b {
+            Grade::B
+        } else if average >= self.c {
+            Grade::C
+        } else {
+            Grade::D
         }
     }
 }
 
+/// The overall result shown on a report card.
+#[derive(Clone, Copy, PartialEq)]
+enum Outcome {
+    Pass,          // A passing grade (and every subject passed, when that rule is on)
+    Fail,          // A failing grade
+    Supplementary, // A passing average, but at least one subject below the pass mark
+    CoreFail,      // A passing average, but a core subject below the core pass mark
+    NotGraded,     // No grade could be determined
+}
+
+impl Outcome {
+    fn as_str(&self) -> &str {
+        match self {
+            Outcome::Pass => "PASS",
+            Outcome::Fail => "FAIL",
+            Outcome::Supplementary => "SUPPLEMENTARY",
+            Outcome::CoreFail => "FAIL (CORE SUBJECT)",
+            Outcome::NotGraded => "N/A",
+        }
+    }
+}
+
+/// A mark that stands in for a letter grade when only letters are known:
+/// the midpoint of the grade's band on the scale (with the default scale
+/// A is 95, B 82.5, C 67.5 and D 30). Incomplete and Invalid have no band and map to 0.
+fn grade_to_representative_mark(grade: &Grade, scale: &GradingScale) -> f64 {
+    match grade {
+        Grade::A => (scale.a + 100.0) / 2.0,
+        Grade::B => (scale.b + scale.a) / 2.0,
+        Grade::C => (scale.c + scale.b) / 2.0,
+        Grade::D => scale.c / 2.0,
+        Grade::Incomplete | Grade::Invalid => 0.0,
+    }
+}
+
+/// Looks up the letter grade for a percentage without needing a Student.
+/// Percentages outside 0-100 are clamped first, so 104.5 is an A and -3 is a D.
+fn grade_from_percentage(pct: f64, scale: &GradingScale) -> Grade {
+    scale.grade_for(pct.clamp(0.0, 100.0))
+}
+
+/// Set by --min-grade and --max-grade: the lowest and highest grade a student's
+/// average can earn, for a cohort whose grades are floored or capped.
+static GRADE_BOUNDS: OnceLock<(Grade, Grade)> = OnceLock::new();
+
 /// ---
 /// ### 3. Implement Student Methods
 /// The impl block associates functions (called "methods" when associated with a struct)
@@ -90,126 +582,5901 @@ impl Student {
     /// Self is a type alias for the struct itself (Student in this case).
     fn new(name: String, total_marks: u32, num_subjects: u32) -> Self {
         Self {
+            student_id: String::new(),
+            section: None,
             name,
             total_marks,
             num_subjects,
+            internal: 0,
+            exam: 0,
+            weights: None,
+            subjects: Vec::new(),
+            override_grade: None,
+            incomplete: false,
+        }
+    }
+
+    /// Creates a student from individual subject marks.
+    /// The total marks and number of subjects are derived from the list.
+    fn with_subjects(name: String, subjects: Vec<Subject>) -> Self {
+        let mut student = Self::new(name, 0, 0);
+        student.subjects = subjects;
+        student.recompute_totals();
+        student
+    }
+
+    /// Collapses subjects entered more than once (ignoring case) into one, at the
+    /// position and with the name of the first, taking the mark `policy` picks.
+    /// Returns the names of the merged subjects, or an error under the Reject
+    /// policy, in which case the student is left unchanged.
+    fn dedup_subjects(&mut self, policy: DuplicateSubjects) -> Result<Vec<String>, DuplicateSubject> {
+        let mut kept: Vec<Subject> = Vec::with_capacity(self.subjects.len());
+        let mut merged: Vec<String> = Vec::new();
+        for subject in &self.subjects {
+            let Some(existing) = kept.iter_mut().find(|kept| kept.name.eq_ignore_ascii_case(&subject.name)) else {
+                kept.push(subject.clone());
+                continue;
+            };
+            match policy {
+                DuplicateSubjects::Reject => return Err(DuplicateSubject(subject.name.clone())),
+                DuplicateSubjects::Latest => existing.mark = subject.mark,
+                DuplicateSubjects::Highest => existing.mark = existing.mark.max(subject.mark),
+            }
+            if !merged.iter().any(|name| name.eq_ignore_ascii_case(&existing.name)) {
+                merged.push(existing.name.clone());
+            }
+        }
+        if !merged.is_empty() {
+            self.subjects = kept;
+            self.recompute_totals();
+        }
+        Ok(merged)
+    }
+
+    /// Re-derives total_marks and num_subjects from the subject list. Absent
+    /// subjects count towards neither, so they stay out of the average.
+    fn recompute_totals(&mut self) {
+        self.total_marks = self.scores().sum();
+        self.num_subjects = self.scores().count() as u32;
+    }
+
+    /// The marks of the subjects the student sat, skipping absences.
+    fn scores(&self) -> impl Iterator<Item = u32> + '_ {
+        self.subjects.iter().filter_map(|subject| subject.mark.score())
+    }
+
+    /// Checks that num_subjects is the number of subjects listed with a mark, for
+    /// records that give both. A student without per-subject marks has nothing to check.
+    fn verify_subject_count(&self) -> Result<(), SubjectCountMismatch> {
+        let actual = self.scores().count() as u32;
+        if self.subjects.is_empty() || actual == self.num_subjects {
+            return Ok(());
+        }
+        Err(SubjectCountMismatch { stated: self.num_subjects, actual })
+    }
+
+    /// Checks that total_marks and num_subjects agree with the subject list,
+    /// for records that give both (such as a JSON export edited by hand).
+    /// A student without per-subject marks has nothing to disagree with.
+    fn verify_totals(&self) -> Result<(), TotalMismatch> {
+        if self.subjects.is_empty() {
+            return Ok(());
+        }
+        let subject_total = self.scores().sum();
+        let subject_count = self.scores().count() as u32;
+        if subject_total == self.total_marks && subject_count == self.num_subjects {
+            return Ok(());
+        }
+        Err(TotalMismatch {
+            stated_total: self.total_marks,
+            stated_subjects: self.num_subjects,
+            subject_total,
+            subject_count,
+        })
+    }
+
+    /// Replaces one subject's mark, e.g. after a retake, and recomputes the totals.
+    /// The subject name is matched case-insensitively.
+    fn update_subject(&mut self, name: &str, new_mark: u32) -> Result<(), SubjectNotFound> {
+        let subject = self
+            .subjects
+            .iter_mut()
+            .find(|subject| subject.name.eq_ignore_ascii_case(name))
+            .ok_or_else(|| SubjectNotFound(name.to_string()))?;
+        subject.mark = Mark::Score(new_mark);
+        self.recompute_totals();
+        Ok(())
+    }
+
+    /// Folds a later record for the same student into this one, e.g. a makeup
+    /// exam that arrived separately. Subjects are matched case-insensitively and
+    /// the higher mark wins; subjects only in `other` are added. Records with only
+    /// totals have their totals and subject counts added together instead.
+    fn merge(&mut self, other: &Student) -> Result<(), MergeError> {
+        if self.student_id != other.student_id {
+            return Err(MergeError::DifferentIds {
+                ours: self.student_id.clone(),
+                theirs: other.student_id.clone(),
+            });
+        }
+        match (self.subjects.is_empty(), other.subjects.is_empty()) {
+            (false, false) => {
+                for resit in &other.subjects {
+                    match self.subjects.iter_mut().find(|subject| subject.name.eq_ignore_ascii_case(&resit.name)) {
+                        Some(subject) => subject.mark = subject.mark.max(resit.mark),
+                        None => self.subjects.push(resit.clone()),
+                    }
+                }
+                self.recompute_totals();
+            }
+            (true, true) => {
+                self.total_marks = self.total_marks.saturating_add(other.total_marks);
+                self.num_subjects = self.num_subjects.saturating_add(other.num_subjects);
+                self.internal = self.internal.saturating_add(other.internal);
+                self.exam = self.exam.saturating_add(other.exam);
+            }
+            _ => return Err(MergeError::MixedMarks),
+        }
+        Ok(())
+    }
+
+    /// Compares the student's subjects with the expected list, ignoring case.
+    /// Missing subjects are reported in curriculum order, then extra ones in
+    /// the order the student took them. An empty result means an exact match.
+    fn validate_against_curriculum(&self, expected: &[String]) -> Vec<CurriculumIssue> {
+        let missing = expected
+            .iter()
+            .filter(|name| !self.subjects.iter().any(|subject| subject.name.eq_ignore_ascii_case(name)))
+            .map(|name| CurriculumIssue::Missing(name.clone()));
+        let extra = self
+            .subjects
+            .iter()
+            .filter(|subject| !expected.iter().any(|name| subject.name.eq_ignore_ascii_case(name)))
+            .map(|subject| CurriculumIssue::Extra(subject.name.clone()));
+        missing.chain(extra).collect()
+    }
+
+    /// Creates a student whose marks are split into internal and exam components.
+    /// The plain total is kept as the raw sum; the average uses the weighted total.
+    fn with_components(name: String, internal: u32, exam: u32, num_subjects: u32, weights: Weights) -> Self {
+        Self {
+            student_id: String::new(),
+            section: None,
+            name,
+            total_marks: internal.saturating_add(exam),
+            num_subjects,
+            internal,
+            exam,
+            weights: Some(weights),
+            subjects: Vec::new(),
+            override_grade: None,
+            incomplete: false,
+        }
+    }
+
+    /// The marks used for averaging: the weighted sum of the internal and exam
+    /// components when weights are set, otherwise the plain total marks.
+    fn weighted_total(&self) -> f64 {
+        match self.weights {
+            Some(w) => self.internal as f64 * w.internal + self.exam as f64 * w.exam,
+            None => self.total_marks as f64,
         }
     }
 
     /// Calculates the average marks for the student.
     /// It performs floating-point division to ensure accuracy.
-    /// Handles the case of num_subjects being 0 to prevent division by zero errors.
-    fn calculate_average(&self) -> f64 {
-        if self.num_subjects == 0 {
-            0.0 // Return 0 if there are no subjects to avoid division by zero
-        } else {
-            // Cast u32 to f64 for floating-point division
-            self.total_marks as f64 / self.num_subjects as f64
+    /// Returns None when num_subjects is 0, so a student with no subjects is never
+    /// mistaken for a student who genuinely averaged 0.
+    /// With `--ignore-zeros` this is average_excluding_zeros instead.
+    fn calculate_average(&self) -> Option<f64> {
+        if IGNORE_ZEROS.load(Ordering::Relaxed) {
+            return self.average_excluding_zeros();
+        }
+        safe_divide(self.weighted_total(), self.num_subjects)
+    }
+
+    /// The marks as a percentage of the most the student could have scored, with
+    /// each subject out of its maximum in `max_marks` (keyed by lower-cased name;
+    /// MAX_SUBJECT_MARK for subjects not listed), so 45 in a subject out of 50 is
+    /// 90%. Absent subjects are left out. Without per-subject marks every subject
+    /// counts as out of MAX_SUBJECT_MARK. None when there is no average.
+    fn percentage(&self, max_marks: &BTreeMap<String, u32>) -> Option<f64> {
+        let average = self.calculate_average()?;
+        if self.subjects.is_empty() {
+            return Some(average * 100.0 / MAX_SUBJECT_MARK as f64);
+        }
+        let (scored, possible) = self
+            .subjects
+            .iter()
+            .filter_map(|subject| {
+                let max = max_marks.get(&subject.name.to_lowercase()).copied().unwrap_or(MAX_SUBJECT_MARK);
+                Some((subject.mark.score()?, max))
+            })
+            .fold((0, 0), |(scored, possible), (mark, max)| (scored + mark, possible + max));
+        safe_divide(scored as f64 * 100.0, possible)
+    }
+
+    /// The student's average as it counts toward class statistics: None for an
+    /// incomplete student, whose marks so far would skew the class figures.
+    fn class_average(&self) -> Option<f64> {
+        if self.incomplete { None } else { self.calculate_average() }
+    }
+
+    /// A letter grade for each subject, graded from its own mark on the same
+    /// scale as the overall grade, in entry order. Absent subjects have no grade
+    /// and are left out. Empty without per-subject marks.
+    fn subject_grades(&self, scale: &GradingScale) -> Vec<(String, Grade)> {
+        self.subjects
+            .iter()
+            .filter_map(|subject| Some((subject.name.clone(), scale.grade_for(subject.mark.score()? as f64))))
+            .collect()
+    }
+
+    /// The student's credit-weighted grade points and credits, summed over their
+    /// subjects. Each subject is graded on `scale` from its own mark and counts
+    /// for its credits in `credits` (keyed by lower-cased subject name), or
+    /// DEFAULT_SUBJECT_CREDITS when it isn't listed.
+    fn credit_points(&self, credits: &BTreeMap<String, u32>, scale: &GradingScale) -> (f64, u32) {
+        let mut points = 0.0;
+        let mut total_credits = 0;
+        for (name, grade) in self.subject_grades(scale) {
+            let subject_credits = credits.get(&name.to_lowercase()).copied().unwrap_or(DEFAULT_SUBJECT_CREDITS);
+            let grade_points = grade.points().unwrap_or(0.0);
+            points += grade_points * subject_credits as f64;
+            total_credits += subject_credits;
+        }
+        (points, total_credits)
+    }
+
+    /// The average over the subjects with a mark above 0, treating a 0 as a
+    /// subject the student missed (e.g. was absent for) rather than failed.
+    /// Returns None when every subject is 0. Without per-subject marks there
+    /// is no way to tell which subjects were 0, so the plain average is used.
+    fn average_excluding_zeros(&self) -> Option<f64> {
+        if self.subjects.is_empty() {
+            return safe_divide(self.weighted_total(), self.num_subjects);
         }
+        let taken: Vec<u32> = self.scores().filter(|mark| *mark > 0).collect();
+        safe_divide(taken.iter().sum::<u32>() as f64, taken.len() as u32)
     }
 
     /// Assigns a grade based on the calculated average marks.
-    /// Follows the specified grading criteria:
+    /// Follows the grading scale in use (see grading_scale), by default:
     /// A: 90+
     /// B: 75-89
     /// C: 60-74
     /// D: Below 60
-    /// Returns Grade::Invalid if the number of subjects is zero.
+    /// Returns Grade::Incomplete whenever the incomplete flag is set, regardless
+    /// of marks, and Grade::Invalid if the number of subjects is zero.
+    /// With --min-grade or --max-grade the grade is then kept within those bounds.
     fn assign_grade(&self) -> Grade {
-        let average = self.calculate_average(); // Get the average marks
-        if self.num_subjects == 0 {
-            Grade::Invalid // Cannot assign a grade if there are no subjects
-        } else if average >= 90.0 {
-            Grade::A
-        } else if average >= 75.0 {
-            Grade::B
-        } else if average >= 60.0 {
-            Grade::C
-        } else {
-            Grade::D
-        }
+        self.assign_grade_on(&grading_scale())
     }
 
-    /// Prints a neatly formatted report card for the student to the console.
-    /// Uses println! macros with formatting specifiers for alignment ({:<15})
-    /// and decimal precision ({:.2}).
-    fn print_report_card(&self) {
-        let average = self.calculate_average(); // Get average
-        let grade = self.assign_grade();         // Get assigned grade
-
-        println!("\n--- Student Report Card ---");
-        println!("{:<15}: {}", "Name", self.name);          // Left-align name, 15 chars wide
-        println!("{:<15}: {}", "Total Marks", self.total_marks);
-        println!("{:<15}: {}", "No. Subjects", self.num_subjects);
-        println!("{:<15}: {:.2}", "Average Marks", average); // .2 for 2 decimal places
-        println!("{:<15}: {}", "Grade", grade.as_str());    // Display grade string
-        println!("---------------------------\n");
+    /// assign_grade on the given scale, such as the one a supporting file was graded with.
+    fn assign_grade_on(&self, scale: &GradingScale) -> Grade {
+        let grade = self.grade_on(scale);
+        match GRADE_BOUNDS.get() {
+            Some(&(min, max)) => grade.clamp(min, max),
+            None => grade,
+        }
     }
-}
 
-/// ---
-/// ### 4. Input Functions
-/// These are helper functions to safely and reliably get input from the user
-/// via the command line. They include basic error handling for invalid input types.
-/// ---
+    /// The grade the student's average earns on the given scale, ignoring any
+    /// manual override. Used to see how a class would fare on another scale.
+    fn grade_on(&self, scale: &GradingScale) -> Grade {
+        if self.incomplete {
+            return Grade::Incomplete;
+        }
+        let Some(average) = self.calculate_average() else {
+            return Grade::Invalid; // Cannot assign a grade if there are no subjects
+        };
+        grade_from_percentage(average, scale)
+    }
 
-/// Reads a single line of text input from the standard input (keyboard).
-/// Returns a Result to indicate success (Ok) or failure (Err) in reading the line.
-fn read_line() -> io::Result<String> {
-    let mut input = String::new(); // Create an empty, mutable String to store input
-    io::stdin().read_line(&mut input)?; // Read line into the string. The ? operator propagates errors.
-    Ok(input.trim().to_string()) // Trim whitespace (like newline characters) and convert to owned String
-}
+    /// How many more total marks the student needs, across their current
+    /// subjects, for their average to reach the `next` grade. Returns Some(0)
+    /// when they are already there, and None when `next` has no cutoff (D or
+    /// Invalid), the student has no subjects, or the marks needed would push
+    /// the total past MAX_SUBJECT_MARK per subject. Marks and subjects are
+    /// counted as calculate_average counts them: weighted, and without zeros
+    /// under --ignore-zeros.
+    fn marks_needed_for(next: Grade, current: &Student, scale: &GradingScale) -> Option<u32> {
+        let cutoff = match next {
+            Grade::A => scale.a,
+            Grade::B => scale.b,
+            Grade::C => scale.c,
+            Grade::D | Grade::Incomplete | Grade::Invalid => return None,
+        };
+        let (marks, counted) = if IGNORE_ZEROS.load(Ordering::Relaxed) && !current.subjects.is_empty() {
+            let taken: Vec<u32> = current.scores().filter(|mark| *mark > 0).collect();
+            (taken.iter().sum::<u32>() as f64, taken.len() as u32)
+        } else {
+            (current.weighted_total(), current.num_subjects)
+        };
+        if counted == 0 {
+            return None;
+        }
+        let needed = (cutoff * counted as f64 - marks).ceil().max(0.0) as u32;
+        let room = counted.saturating_mul(MAX_SUBJECT_MARK) as f64 - marks;
+        if needed as f64 > room.ceil() {
+            return None; // Out of reach even with full marks
+        }
+        Some(needed)
+    }
 
-/// Prompts the user for a string input and ensures it's not empty.
-/// Loops until valid (non-empty) input is provided.
-fn get_string_input(prompt: &str) -> String {
-    loop { // Infinite loop until valid input is received
-        print!("{}", prompt); // Display the prompt to the user
-        io::stdout().flush().expect("Failed to flush stdout"); // Crucial to ensure the prompt is displayed before waiting for input
+    /// How far the student's average sits below the cutoff of the next higher
+    /// band on `scale`, e.g. 2.5 for a 72.5 average with B starting at 75.
+    /// None when they already have the top grade, have no average, or are incomplete.
+    fn distance_to_next_grade(&self, scale: &GradingScale) -> Option<f64> {
+        let average = self.class_average()?;
+        let cutoff = match scale.grade_for(average).next_up()? {
+            Grade::A => scale.a,
+            Grade::B => scale.b,
+            _ => scale.c,
+        };
+        Some(cutoff - average)
+    }
 
-        match read_line() { // Attempt to read a line
-            Ok(input) if !input.is_empty() => return input, // If successful and not empty, return the input
-            _ => println!("Input cannot be empty. Please try again."), // Otherwise, print error and loop again
+    /// A nudge such as "2.50 marks from a B" for a student whose average is
+    /// within `window` marks of the next grade. None outside the window, for a
+    /// grade set by hand, or when distance_to_next_grade has nothing to offer.
+    fn next_grade_hint(&self, scale: &GradingScale, window: f64, format: &ReportFormat) -> Option<String> {
+        if self.override_grade.is_some() {
+            return None; // The computed grade isn't the one on the card
         }
+        let distance = self.distance_to_next_grade(scale).filter(|distance| *distance <= window)?;
+        let next = scale.grade_for(self.class_average()?).next_up()?;
+        let article = if next == Grade::A { "an" } else { "a" };
+        Some(format!("{} marks from {} {}", format.average(distance), article, next.as_str()))
     }
-}
 
-/// Prompts the user for an unsigned 32-bit integer (u32) input.
-/// Loops until a valid number is entered. Includes error handling for non-numeric input.
-fn get_u32_input(prompt: &str) -> u32 {
-    loop { // Infinite loop until valid input is received
-        print!("{}", prompt); // Display the prompt
-        io::stdout().flush().expect("Failed to flush stdout"); // Flush stdout
+    /// The grade that counts: a manual override when one is set, otherwise the
+    /// grade computed from the average.
+    fn final_grade(&self) -> Grade {
+        self.override_grade.unwrap_or_else(|| self.assign_grade())
+    }
 
-        let input = read_line().expect("Failed to read line"); // Read input, panic if unable to read
+    /// final_grade on the given scale, such as the one a supporting file was graded with.
+    fn final_grade_on(&self, scale: &GradingScale) -> Grade {
+        self.override_grade.unwrap_or_else(|| self.assign_grade_on(scale))
+    }
+
+    /// The subjects whose mark is below their pass mark: the one `pass_marks`
+    /// gives them (keyed by lower-cased subject name), or else `pass_mark`.
+    /// Absent subjects have no mark to judge and are never failing.
+    fn failing_subjects(&self, pass_mark: f64, pass_marks: &BTreeMap<String, f64>) -> Vec<&Subject> {
+        self.subjects
+            .iter()
+            .filter(|subject| {
+                let required = pass_marks.get(&subject.name.to_lowercase()).copied().unwrap_or(pass_mark);
+                subject.mark.score().is_some_and(|mark| (mark as f64) < required)
+            })
+            .collect()
+    }
 
-        match input.parse::<u32>() { // Attempt to parse the string input into a u32
-            Ok(num) => return num, // If parsing is successful, return the number
-            Err(_) => println!("Invalid input. Please enter a valid number."), // If parsing fails (e.g., not a number), print error and loop
+    /// Why the student has their grade, in a sentence or two, such as "Average
+    /// 82.50 falls in the B band (75 to below 90)." A grade then changed by hand
+    /// or by --min-grade/--max-grade is explained too.
+    fn grade_justification(&self, scale: &GradingScale, format: &ReportFormat) -> String {
+        let earned = self.grade_on(scale);
+        let mut text = match (earned, self.calculate_average()) {
+            (Grade::Incomplete, _) => "The course isn't finished, so the grade is I for now.".to_string(),
+            (_, None) => "No grade could be computed: there are no subject marks to average.".to_string(),
+            (_, Some(average)) => format!(
+                "Average {} falls in the {} band ({}).",
+                format.average(average),
+                earned.as_str(),
+                scale.band(earned).unwrap_or_default()
+            ),
+        };
+        let grade = self.final_grade();
+        if self.override_grade.is_some() && grade != earned {
+            text.push_str(&format!(" The grade was then set to {} by hand.", grade.as_str()));
+        } else if grade != earned {
+            let (change, option) = if grade > earned { ("raised", "--min-grade") } else { ("lowered", "--max-grade") };
+            text.push_str(&format!(" It was {} to {} by {}.", change, grade.as_str(), option));
         }
+        text
     }
-}
 
-/// ---
-/// ### 5. Main Application Logic
-/// This is the entry point of the program. Execution begins here.
-/// It orchestrates the flow: welcoming the user, getting student details,
-/// creating a Student object, and then printing the report card.
-/// ---
-fn main() {
-    println!("Welcome to the Student Report Card Generator!");
+    /// True when every subject's mark reaches its pass mark (see failing_subjects).
+    /// Students without per-subject marks have nothing to fail, so they pass.
+    fn all_subjects_pass(&self, pass_mark: f64, pass_marks: &BTreeMap<String, f64>) -> bool {
+        self.failing_subjects(pass_mark, pass_marks).is_empty()
+    }
 
-    // Get student details using our helper functions
-    let name = get_string_input("Enter student's name: ");
-    let total_marks = get_u32_input("Enter total marks: ");
-    let num_subjects = get_u32_input("Enter number of subjects: ");
+    /// True when every core subject (matched by name, ignoring case) reaches
+    /// `core_pass_mark`. Electives and absences aren't checked, and students
+    /// without per-subject marks have no core subjects to fail.
+    fn core_subjects_pass(&self, core_subjects: &[String], core_pass_mark: f64) -> bool {
+        self.subjects
+            .iter()
+            .filter(|subject| core_subjects.iter().any(|core| core.eq_ignore_ascii_case(&subject.name)))
+            .filter_map(|subject| subject.mark.score())
+            .all(|mark| mark as f64 >= core_pass_mark)
+    }
 
-    // Create a new Student instance with the collected data
-    let student = Student::new(name, total_marks, num_subjects);
+    /// The overall result. A passing grade becomes CoreFail when a core subject
+    /// falls below the core pass mark, or else Supplementary when the config
+    /// requires every subject to pass and at least one subject fell short.
+    fn outcome(&self, config: &Config) -> Outcome {
+        let grade = self.final_grade();
+        if grade.is_passing() {
+            if let Some(core) = &config.core
+                && !self.core_subjects_pass(&core.subjects, core.pass_mark)
+            {
+                return Outcome::CoreFail;
+            }
+            match config.subject_pass_mark {
+                Some(pass_mark) if !self.all_subjects_pass(pass_mark, &config.subject_pass_marks) => {
+                    Outcome::Supplementary
+                }
+                _ => Outcome::Pass,
+            }
+        } else if grade.is_failing() {
+            Outcome::Fail
+        } else {
+            Outcome::NotGraded // No grade, so no pass/fail
+        }
+    }
 
-    // Print the report card for the created student
-    student.print_report_card();
+    /// The label/value pairs shown on the report card below the student's name,
+    /// in display order. Shared by the console report and the file exporters so
+    /// they never drift apart; each renderer prints the name itself. With
+    /// `grades_only` that is just the grade.
+    fn report_fields(&self, config: &Config) -> Vec<(String, String)> {
+        let format = &config.format;
+        let average = self.calculate_average(); // Get average
+        let grade = self.final_grade();          // Get assigned grade, honouring any override
+        let earned = self.grade_on(&grading_scale()); // Before --min-grade and --max-grade
+        let grade_text = match self.override_grade {
+            Some(_) => format!("{} (manually adjusted)", grade.as_str()),
+            None if grade > earned => format!("{} (raised from {})", grade.as_str(), earned.as_str()),
+            None if grade < earned => format!("{} (lowered from {})", grade.as_str(), earned.as_str()),
+            None => grade.as_str().to_string(), // Display grade string
+        };
+        if format.grades_only {
+            return vec![("Grade".to_string(), grade_text)]; // Nothing that would reveal a score
+        }
 
-    println!("Thank you for using the Student Report Card Generator!");
+        let mut fields = Vec::new();
+        if !self.student_id.is_empty() {
+            fields.push(("Student ID".to_string(), self.student_id.clone()));
+        }
+        if let Some(section) = &self.section {
+            fields.push(("Section".to_string(), section.clone()));
+        }
+        let scale = grading_scale();
+        for subject in &self.subjects {
+            // One line per subject, with its own grade to show strengths and weaknesses
+            let value = match subject.mark {
+                Mark::Score(mark) => format!("{} ({})", mark, scale.grade_for(mark as f64).as_str()),
+                Mark::Absent => subject.mark.to_string(),
+            };
+            fields.push((subject.name.clone(), value));
+        }
+        if let Some(w) = self.weights {
+            // Show both components with their weights, then the combined total
+            fields.push(("Internal".to_string(), format!("{} ({:.0}%)", self.internal, w.internal * 100.0)));
+            fields.push(("Exam".to_string(), format!("{} ({:.0}%)", self.exam, w.exam * 100.0)));
+            fields.push(("Weighted Total".to_string(), format.average(self.weighted_total())));
+        } else {
+            fields.push(("Total Marks".to_string(), self.total_marks.to_string()));
+        }
+        fields.push(("No. Subjects".to_string(), self.num_subjects.to_string()));
+        fields.push(("Average Marks".to_string(), match average {
+            Some(average) => format.average(average), // Uses the configured decimal places
+            None => "N/A".to_string(),                  // No subjects, so no average
+        }));
+        fields.push(("Grade".to_string(), grade_text));
+        if config.explain {
+            fields.push(("Why".to_string(), self.grade_justification(&grading_scale(), format)));
+        }
+        fields.push(("Result".to_string(), self.outcome(config).as_str().to_string()));
+        fields
+    }
+
+    /// The student on a single line for logs and grep, e.g.
+    /// "John Smith | 315/4 | 78.75 | B" (name, total/subjects, average, grade).
+    /// The average uses the configured precision and long names are shortened
+    /// as on the report card; nothing is padded. With `grades_only` the line is
+    /// just "John Smith | B".
+    fn format_compact(&self, format: &ReportFormat) -> String {
+        if format.grades_only {
+            return format!("{} | {}", format.display_name(&self.name), self.final_grade().as_str());
+        }
+        let average = self
+            .calculate_average()
+            .map(|average| format.average(average))
+            .unwrap_or_else(|| "N/A".to_string());
+        format!(
+            "{} | {}/{} | {} | {}",
+            format.display_name(&self.name),
+            self.total_marks,
+            self.num_subjects,
+            average,
+            self.final_grade().as_str()
+        )
+    }
+
+    /// Writes a neatly formatted report card for the student.
+    /// Uses writeln! macros with formatting specifiers for alignment ({:<15}).
+    /// The Result line is colored green for PASS, yellow for SUPPLEMENTARY and
+    /// red for FAIL, and long names are shortened so they can't break the layout.
+    /// Write errors, such as a closed pipe, are returned rather than panicking.
+    fn write_report_card(&self, writer: &mut impl Write, config: &Config) -> io::Result<()> {
+        let result_color = match self.outcome(config) {
+            Outcome::Pass => Some(GREEN),
+            Outcome::Supplementary => Some(YELLOW),
+            Outcome::Fail | Outcome::CoreFail => Some(RED),
+            Outcome::NotGraded => None,
+        };
+
+        writeln!(writer, "\n--- Student Report Card ---")?;
+        writeln!(writer, "{:<15}: {}", "Name", config.format.display_name(&self.name))?;
+        for (label, value) in self.report_fields(config) {
+            let value = match result_color {
+                Some(color) if label == "Result" => colorize(&value, color),
+                _ => value,
+            };
+            writeln!(writer, "{:<15}: {}", label, value)?; // Left-align labels, 15 chars wide
+        }
+        let hint = config
+            .grade_hint_window
+            .filter(|_| !config.format.grades_only)
+            .and_then(|window| self.next_grade_hint(&grading_scale(), window, &config.format));
+        if let Some(hint) = hint {
+            writeln!(writer, "{:<15}: {}", "Next Grade", hint)?;
+        }
+        writeln!(writer, "---------------------------\n")
+    }
+
+    /// The report card as text, for pasting into an email or another document.
+    fn report_string(&self, config: &Config) -> String {
+        let mut text = Vec::new();
+        self.write_report_card(&mut text, config).expect("writing to memory cannot fail");
+        String::from_utf8_lossy(&text).into_owned()
+    }
+
+    /// Prints the report card to the console during interactive entry.
+    fn print_report_card(&self, config: &Config) {
+        // A failed write to stdout has nowhere to be reported, so it is ignored
+        let _ = self.write_report_card(&mut io::stdout(), config);
+    }
+}
+
+/// Set by `--ignore-zeros`: averages leave out subjects marked 0.
+static IGNORE_ZEROS: AtomicBool = AtomicBool::new(false);
+
+/// Divides a total by a count, returning None instead of dividing by zero.
+fn safe_divide(total: f64, count: u32) -> Option<f64> {
+    if count == 0 {
+        None
+    } else {
+        Some(total / count as f64) // Cast u32 to f64 for floating-point division
+    }
+}
+
+/// The most decimal places an average can be shown with.
+const MAX_PRECISION: usize = 6;
+
+/// Presentation settings shared by the console report and every exporter.
+#[derive(Clone, Copy)]
+struct ReportFormat {
+    precision: usize,       // Decimal places for averages (0 to MAX_PRECISION)
+    max_name_length: usize, // Longer names are cut short with "…" on screen
+    grades_only: bool,      // --grades-only: show each student's letter grade but no marks or average
+    trim_zeros: bool,       // --trim-zeros: print 80.00 as "80" and 78.50 as "78.5"
+}
+
+impl Default for ReportFormat {
+    fn default() -> Self {
+        Self {
+            precision: 2,
+            max_name_length: 40,
+            grades_only: false,
+            trim_zeros: false,
+        }
+    }
+}
+
+impl ReportFormat {
+    /// Formats an average with the configured number of decimal places.
+    /// With precision 0 whole numbers are printed without a trailing dot ("79").
+    /// With trim_zeros, zeros at the end of the decimals are dropped too.
+    fn average(&self, value: f64) -> String {
+        let text = format!("{:.*}", self.precision, value);
+        if self.trim_zeros { trim_zeros(&text) } else { text }
+    }
+
+    /// Shortens a name for on-screen display, ending it with "…" when it is
+    /// longer than max_name_length characters. Exports keep the full name.
+    fn display_name(&self, name: &str) -> String {
+        if name.chars().count() <= self.max_name_length {
+            return name.to_string();
+        }
+        let kept: String = name.chars().take(self.max_name_length.saturating_sub(1)).collect();
+        format!("{}…", kept)
+    }
+}
+
+/// Strips trailing zeros after the decimal point, and then the point itself if
+/// nothing is left after it: "80.00" becomes "80" and "78.50" becomes "78.5".
+/// Numbers without a decimal point are returned unchanged.
+fn trim_zeros(number: &str) -> String {
+    if !number.contains('.') {
+        return number.to_string();
+    }
+    number.trim_end_matches('0').trim_end_matches('.').to_string()
+}
+
+/// Parses a precision setting, accepting whole numbers from 0 to MAX_PRECISION.
+fn parse_precision(value: &str) -> Option<usize> {
+    value.parse::<usize>().ok().filter(|precision| *precision <= MAX_PRECISION)
+}
+
+// ---
+// ### 4. Input Functions
+// These are helper functions to safely and reliably get input from the user
+// via the command line. They include basic error handling for invalid input types.
+// ---
+
+/// Reads a single line of text input from the standard input (keyboard).
+/// Returns a Result to indicate success (Ok) or failure (Err) in reading the line.
+fn read_line() -> io::Result<String> {
+    let answer = read_answer(&mut io::stdin().lock());
+    #[cfg(feature = "ctrlc")]
+    if INTERRUPTED.load(Ordering::Relaxed) {
+        save_session_and_exit(); // Ctrl-C was pressed while waiting; this Enter accepts the offer to save
+    }
+    answer
+}
+
+/// Reads one line from `reader` with the surrounding whitespace (like the
+/// newline) trimmed. The end of input (e.g. Ctrl-D or a piped file running
+/// out) is an UnexpectedEof error, since no more answers will come.
+fn read_answer(reader: &mut impl BufRead) -> io::Result<String> {
+    let mut input = String::new(); // Create an empty, mutable String to store input
+    if reader.read_line(&mut input)? == 0 {
+        return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "no more input"));
+    }
+    Ok(input.trim().to_string())
+}
+
+/// Set by --non-interactive, to how errors are reported: any prompt then fails
+/// at once instead of waiting for an answer that will never come.
+static NON_INTERACTIVE: OnceLock<ErrorFormat> = OnceLock::new();
+
+/// The exit status when --non-interactive stops the program at a prompt, or
+/// input ends before a required answer, so automation can tell missing input
+/// apart from other errors (status 1).
+const EXIT_INPUT_NEEDED: i32 = 3;
+
+/// Reported when --non-interactive is set and the program needs an answer.
+#[derive(Debug)]
+struct InputNeeded {
+    prompt: String, // What was being asked, e.g. "Enter student ID:"
+}
+
+impl std::fmt::Display for InputNeeded {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "input needed for '{}', but --non-interactive is set", self.prompt)
+    }
+}
+
+impl ErrorDetails for InputNeeded {
+    fn kind(&self) -> &'static str {
+        "InputNeeded"
+    }
+
+    fn fields(&self) -> Vec<(&'static str, String)> {
+        vec![("prompt", json_string(&self.prompt))]
+    }
+}
+
+/// Shows a prompt and reads the answer. Every question the program asks goes
+/// through here, so --non-interactive can stop it before it waits on stdin.
+/// Failing to show the prompt (e.g. stdout is a closed pipe) is an error too.
+fn ask(prompt: &str) -> io::Result<String> {
+    if let Some(format) = NON_INTERACTIVE.get() {
+        report_error(*format, &InputNeeded { prompt: prompt.trim().to_string() });
+        process::exit(EXIT_INPUT_NEEDED);
+    }
+    write_prompt(&mut io::stdout().lock(), prompt)?;
+    read_line()
+}
+
+/// Writes a prompt and flushes it, which is crucial to ensure the prompt is
+/// displayed before waiting for input.
+fn write_prompt(writer: &mut impl Write, prompt: &str) -> io::Result<()> {
+    write!(writer, "{}", prompt)?;
+    writer.flush()
+}
+
+/// Prints a line of the interactive session. A failed write is ignored, as in
+/// print_report_card: the next prompt meets the same error in ask and stops
+/// the program cleanly, instead of println! panicking here.
+fn say(line: &str) {
+    let _ = writeln!(io::stdout(), "{}", line);
+}
+
+/// Asks for an answer the program can't go on without. When there will be no
+/// answer, because input has ended or the prompt can't be shown, the program
+/// stops through input_closed rather than panicking or asking forever.
+fn ask_required(prompt: &str) -> String {
+    ask(prompt).unwrap_or_else(|err| input_closed(err))
+}
+
+/// Stops the program when a prompt fails. The end of input exits with
+/// EXIT_INPUT_NEEDED, like --non-interactive; a closed stdout (e.g. piped into
+/// `head`) exits quietly, as when printing reports. Anything entered so far is
+/// kept in the autosave, to be recovered on the next run.
+fn input_closed(err: io::Error) -> ! {
+    match err.kind() {
+        io::ErrorKind::UnexpectedEof => {
+            eprintln!("\nInput ended before the question was answered; stopping.");
+            process::exit(EXIT_INPUT_NEEDED);
+        }
+        io::ErrorKind::BrokenPipe => process::exit(0),
+        _ => {
+            eprintln!("\nError: could not read input: {}", err);
+            process::exit(1);
+        }
+    }
+}
+
+/// What an empty answer to a text prompt means. Set per field in the config,
+/// e.g. `empty_name = default:Anonymous`.
+#[derive(Clone, Debug, Default, PartialEq)]
+enum EmptyInput {
+    #[default]
+    Reject,          // Re-prompt until something is typed
+    Default(String), // Use this value instead (shown in brackets in the prompt)
+    None,            // Leave the field unset
+}
+
+impl EmptyInput {
+    /// Parses a config value: `reject`, `none`, or `default:<text>`.
+    fn parse(value: &str) -> Option<EmptyInput> {
+        match value {
+            "reject" => Some(EmptyInput::Reject),
+            "none" => Some(EmptyInput::None),
+            _ => {
+                let default = value.strip_prefix("default:")?.trim();
+                (!default.is_empty()).then(|| EmptyInput::Default(default.to_string()))
+            }
+        }
+    }
+}
+
+/// The empty-input policy for each text field asked for when entering a student.
+#[derive(Clone, Debug)]
+struct InputPolicies {
+    name: EmptyInput,       // Rejected by default; `none` isn't allowed, since every student needs a name
+    student_id: EmptyInput, // Rejected by default
+    section: EmptyInput,    // None by default: most classes aren't split into sections
+}
+
+impl Default for InputPolicies {
+    fn default() -> Self {
+        Self {
+            name: EmptyInput::Reject,
+            student_id: EmptyInput::Reject,
+            section: EmptyInput::None,
+        }
+    }
+}
+
+/// Prompts the user for a string input, handling an empty answer as `policy`
+/// says: re-prompting until something is typed, substituting the default, or
+/// returning None. The end of input isn't an empty answer: it stops the
+/// program (see ask_required), since re-prompting would never get one.
+fn get_string_input(prompt: &str, policy: &EmptyInput) -> Option<String> {
+    let prompt = match policy {
+        EmptyInput::Reject => prompt.to_string(),
+        EmptyInput::Default(default) => prompt_with_default(prompt, default),
+        EmptyInput::None => format!("{} (leave blank for none): ", prompt.trim_end().trim_end_matches(':')),
+    };
+    loop { // Loop until the answer is acceptable under the policy
+        let input = ask_required(&prompt);
+        match policy {
+            _ if !input.is_empty() => return Some(input), // Anything typed is taken as is
+            EmptyInput::Default(default) => return Some(default.clone()),
+            EmptyInput::None => return None,
+            EmptyInput::Reject => say("Input cannot be empty. Please try again."), // Print error and loop again
+        }
+    }
+}
+
+/// Prompts for text that must not be empty, whatever the config says; used for
+/// answers such as subject names and menu lookups that have no sensible default.
+fn get_required_input(prompt: &str) -> String {
+    get_string_input(prompt, &EmptyInput::Reject).unwrap_or_default() // Reject never yields None
+}
+
+/// Parses a whole number, allowing commas or underscores as thousands separators
+/// ("1,200" or "1_200"). Separators must split the digits into groups of three,
+/// so malformed input such as "1,2,3" or "12,34" is rejected.
+fn parse_u32(input: &str) -> Option<u32> {
+    let separator = match (input.contains(','), input.contains('_')) {
+        (false, false) => return input.parse::<u32>().ok(), // Plain number
+        (true, false) => ',',
+        (false, true) => '_',
+        (true, true) => return None, // Mixing separators is never valid
+    };
+
+    let groups: Vec<&str> = input.split(separator).collect();
+    let all_digits = |group: &str| group.chars().all(|c| c.is_ascii_digit());
+    let first_ok = (1..=3).contains(&groups[0].len()) && all_digits(groups[0]);
+    let rest_ok = groups[1..].iter().all(|group| group.len() == 3 && all_digits(group));
+    if !first_ok || !rest_ok {
+        return None;
+    }
+    groups.concat().parse::<u32>().ok()
+}
+
+/// Why a value entered for a student was rejected. The same checks run for
+/// interactive entry and CSV import; each reports the error its own way.
+#[derive(Debug, PartialEq)]
+enum ValidationError {
+    EmptyName,                                 // Every student needs a name
+    InvalidNumber(String),                     // Not a whole number of at least 0
+    OutOfRange { value: u32, min: u32, max: u32 }, // A number outside the allowed range
+    MarksExceedMax { total: u32, max: u32 },   // More marks than the subjects could give
+    TooFewSubjects,                            // A student must take at least one subject
+}
+
+impl std::fmt::Display for ValidationError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            ValidationError::EmptyName => write!(f, "the name cannot be empty"),
+            ValidationError::InvalidNumber(value) => write!(f, "'{}' is not a valid number", value),
+            ValidationError::OutOfRange { value, min, max } => {
+                write!(f, "{} is not between {} and {}", value, min, max)
+            }
+            ValidationError::MarksExceedMax { total, max } => {
+                write!(f, "total marks of {} are more than the {} possible", total, max)
+            }
+            ValidationError::TooFewSubjects => write!(f, "there must be at least one subject"),
+        }
+    }
+}
+
+impl ValidationError {
+    /// The variant name, used as the error kind in JSON error output.
+    fn kind(&self) -> &'static str {
+        match self {
+            ValidationError::EmptyName => "EmptyName",
+            ValidationError::InvalidNumber(_) => "InvalidNumber",
+            ValidationError::OutOfRange { .. } => "OutOfRange",
+            ValidationError::MarksExceedMax { .. } => "MarksExceedMax",
+            ValidationError::TooFewSubjects => "TooFewSubjects",
+        }
+    }
+
+    /// The values behind the error, for JSON error output.
+    fn fields(&self) -> Vec<(&'static str, String)> {
+        match self {
+            ValidationError::EmptyName | ValidationError::TooFewSubjects => Vec::new(),
+            ValidationError::InvalidNumber(value) => vec![("value", json_string(value))],
+            ValidationError::OutOfRange { value, min, max } => {
+                vec![("value", value.to_string()), ("min", min.to_string()), ("max", max.to_string())]
+            }
+            ValidationError::MarksExceedMax { total, max } => {
+                vec![("total", total.to_string()), ("max", max.to_string())]
+            }
+        }
+    }
+}
+
+/// The most subjects a single student can be entered with.
+const MAX_SUBJECTS: u32 = 50;
+
+/// The highest mark a single subject can have.
+const MAX_SUBJECT_MARK: u32 = 100;
+
+/// Parses a percentage from 0 to 100, optionally followed by '%' ("72.5" or "72.5%").
+/// Returns None for anything else, including values outside the range.
+fn parse_percent(s: &str) -> Option<f64> {
+    let s = s.trim();
+    let number = s.strip_suffix('%').unwrap_or(s).trim_end();
+    number.parse::<f64>().ok().filter(|percent| (0.0..=100.0).contains(percent))
+}
+
+/// Checks that a student's name isn't blank.
+fn check_name(name: &str) -> Result<(), ValidationError> {
+    if name.trim().is_empty() { Err(ValidationError::EmptyName) } else { Ok(()) }
+}
+
+/// Checks that a number lies between min and max (inclusive).
+fn check_range(value: u32, min: u32, max: u32) -> Result<u32, ValidationError> {
+    if (min..=max).contains(&value) { Ok(value) } else { Err(ValidationError::OutOfRange { value, min, max }) }
+}
+
+/// Checks a number of subjects entered by hand: at least one, at most MAX_SUBJECTS.
+fn check_subject_count(count: u32) -> Result<u32, ValidationError> {
+    if count == 0 {
+        return Err(ValidationError::TooFewSubjects);
+    }
+    check_range(count, 1, MAX_SUBJECTS)
+}
+
+/// Checks that a total could have come from the given number of subjects,
+/// each worth at most MAX_SUBJECT_MARK.
+fn check_total_marks(total: u32, num_subjects: u32) -> Result<(), ValidationError> {
+    let max = num_subjects.saturating_mul(MAX_SUBJECT_MARK);
+    if total > max { Err(ValidationError::MarksExceedMax { total, max }) } else { Ok(()) }
+}
+
+/// Prompts the user for an unsigned 32-bit integer (u32) input.
+/// Loops until a valid number is entered, echoing back anything that isn't one.
+fn get_u32_input(prompt: &str) -> u32 {
+    get_checked_u32(prompt, Ok)
+}
+
+/// Prompts for a u32 and runs it through `check`, re-prompting with the
+/// validation error (e.g. "'x' is not a valid number. Please try again.")
+/// until a number passes.
+fn get_checked_u32(prompt: &str, check: impl Fn(u32) -> Result<u32, ValidationError>) -> u32 {
+    loop { // Infinite loop until valid input is received
+        let input = ask_required(prompt);
+
+        let checked = parse_u32(&input).ok_or(ValidationError::InvalidNumber(input)).and_then(&check);
+        match checked {
+            Ok(num) => return num, // Parsed and within bounds
+            Err(err) => say(&format!("{}. Please try again.", err)), // Explain what was wrong and loop
+        }
+    }
+}
+
+/// Prompts for a u32 from `min` to `max` (inclusive), re-prompting with
+/// "Enter a number between MIN and MAX." until one is typed.
+fn get_u32_in_range(prompt: &str, min: u32, max: u32) -> u32 {
+    loop {
+        match check_range(get_u32_input(prompt), min, max) {
+            Ok(value) => return value,
+            Err(_) => say(&format!("Enter a number between {} and {}.", min, max)),
+        }
+    }
+}
+
+/// Prompts for a percentage from 0 to 100, with or without a trailing '%'
+/// (see parse_percent), re-prompting with "Enter a value between 0 and 100."
+/// until one is typed.
+fn get_percent_input(prompt: &str) -> f64 {
+    loop {
+        match parse_percent(&ask_required(prompt)) {
+            Some(percent) => return percent,
+            None => say("Enter a value between 0 and 100."),
+        }
+    }
+}
+
+/// Prompts for the number of subjects a student took, from 1 to MAX_SUBJECTS.
+fn get_subject_count() -> u32 {
+    get_u32_in_range("Enter number of subjects: ", 1, MAX_SUBJECTS)
+}
+
+/// Why a mark written as a fraction such as "45/50" could not be read.
+#[derive(Debug)]
+enum FractionError {
+    Malformed(String),                        // Not two whole numbers separated by '/'
+    ZeroDenominator,                          // "45/0" has no maximum to divide by
+    AboveMaximum { obtained: u32, max: u32 }, // "55/50" is more than the paper was worth
+}
+
+impl std::fmt::Display for FractionError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            FractionError::Malformed(text) => write!(f, "'{}' is not a mark like 45/50", text),
+            FractionError::ZeroDenominator => write!(f, "the maximum mark cannot be 0"),
+            FractionError::AboveMaximum { obtained, max } => {
+                write!(f, "{} is more than the maximum of {}", obtained, max)
+            }
+        }
+    }
+}
+
+/// Parses a mark recorded against its own maximum, such as "45/50" or
+/// "45 / 50", into (obtained, max). Both parts are whole numbers (thousands
+/// separators allowed); the maximum must be above 0 and at least the mark.
+fn parse_fraction_mark(s: &str) -> Result<(u32, u32), FractionError> {
+    let malformed = || FractionError::Malformed(s.trim().to_string());
+    let (obtained, max) = s.split_once('/').ok_or_else(malformed)?;
+    let obtained = parse_u32(obtained.trim()).ok_or_else(malformed)?;
+    let max = parse_u32(max.trim()).ok_or_else(malformed)?;
+    if max == 0 {
+        return Err(FractionError::ZeroDenominator);
+    }
+    if obtained > max {
+        return Err(FractionError::AboveMaximum { obtained, max });
+    }
+    Ok((obtained, max))
+}
+
+/// Scales a fractional mark to the usual 0-MAX_SUBJECT_MARK range, rounding to
+/// the nearest whole mark, so 45/50 is 90 and 2/3 is 67.
+fn fraction_to_mark((obtained, max): (u32, u32)) -> u32 {
+    (obtained as f64 / max as f64 * MAX_SUBJECT_MARK as f64).round() as u32
+}
+
+/// Prompts for a single subject's mark, either as a plain mark from 0 to
+/// MAX_SUBJECT_MARK or as a fraction of the paper's own maximum ("45/50"),
+/// which is converted to a percentage. Re-prompts until one of them is valid.
+fn get_mark_input(prompt: &str) -> u32 {
+    loop {
+        let input = ask_required(prompt);
+        match parse_mark_input(&input) {
+            Ok(mark) => return mark,
+            Err(message) => say(&message),
+        }
+    }
+}
+
+/// Reads a typed mark, either a plain one or a fraction such as 45/50 (see
+/// get_mark_input). The error is the message to show before asking again.
+fn parse_mark_input(input: &str) -> Result<u32, String> {
+    if input.contains('/') {
+        return parse_fraction_mark(input)
+            .map(fraction_to_mark)
+            .map_err(|err| format!("Invalid mark: {}. Please try again.", err));
+    }
+    parse_u32(input)
+        .ok_or(ValidationError::InvalidNumber(input.to_string()))
+        .and_then(|mark| check_range(mark, 0, MAX_SUBJECT_MARK))
+        .map_err(|err| format!("{}. Enter a mark, or one out of its maximum like 45/50.", err))
+}
+
+/// Prompts for a subject's mark like get_mark_input, also accepting "AB" or
+/// "absent" for a subject the student was absent for. With a `current` mark,
+/// an empty answer keeps it.
+fn get_subject_mark_input(subject: &str, current: Option<Mark>) -> Mark {
+    let prompt = format!("{} mark (AB if absent): ", subject);
+    let prompt = match current {
+        Some(mark) => prompt_with_default(&prompt, &mark.to_string()),
+        None => prompt,
+    };
+    loop {
+        let input = ask_required(&prompt);
+        if let (Some(mark), true) = (current, input.is_empty()) {
+            return mark; // Empty line keeps the current value
+        }
+        if Mark::is_absent_text(&input) {
+            return Mark::Absent;
+        }
+        match parse_mark_input(&input) {
+            Ok(mark) => return Mark::Score(mark),
+            Err(message) => say(&message),
+        }
+    }
+}
+
+/// Builds a prompt that shows a default value in brackets, e.g. "Enter total marks [315]: ".
+/// Any trailing ": " on the original prompt is moved after the brackets.
+fn prompt_with_default(prompt: &str, default: &str) -> String {
+    let base = prompt.trim_end().trim_end_matches(':'); // Drop the trailing colon so it can follow the default
+    format!("{} [{}]: ", base, default)
+}
+
+/// Prompts the user for a string input, showing a default value in brackets.
+/// Pressing Enter on an empty line returns the default instead of re-prompting.
+fn get_string_input_default(prompt: &str, default: &str) -> String {
+    let input = ask_required(&prompt_with_default(prompt, default));
+    if input.is_empty() { default.to_string() } else { input } // An empty line keeps the default
+}
+
+/// Prompts the user for a u32 input, showing a default value in brackets.
+/// Pressing Enter on an empty line returns the default; invalid numbers re-prompt.
+fn get_u32_input_default(prompt: &str, default: u32) -> u32 {
+    let full_prompt = prompt_with_default(prompt, &default.to_string());
+    loop {
+        let input = ask_required(&full_prompt);
+        if input.is_empty() {
+            return default; // Empty line keeps the current value
+        }
+
+        match parse_u32(&input) {
+            Some(num) => return num,
+            None => say(&format!("{}. Please try again.", ValidationError::InvalidNumber(input))),
+        }
+    }
+}
+
+/// Like get_u32_in_range, but pressing Enter keeps `default`, as in
+/// get_u32_input_default. A default outside the range isn't accepted either.
+fn get_u32_in_range_default(prompt: &str, default: u32, min: u32, max: u32) -> u32 {
+    loop {
+        match check_range(get_u32_input_default(prompt, default), min, max) {
+            Ok(value) => return value,
+            Err(_) => say(&format!("Enter a number between {} and {}.", min, max)),
+        }
+    }
+}
+
+/// Asks a yes/no question, accepting "y", "yes", "n" or "no" in any case and
+/// re-prompting on anything else. An empty answer takes `default` when there is
+/// one, which the prompt shows capitalised: "Proceed? (Y/n): ". Without a default
+/// the user must answer. The end of input counts as the default, or as "no";
+/// other failures stop the program through input_closed.
+fn get_yes_no(question: &str, default: Option<bool>) -> bool {
+    let choices = match default {
+        Some(true) => "Y/n",
+        Some(false) => "y/N",
+        None => "y/n",
+    };
+    loop {
+        let answer = match ask(&format!("{} ({}): ", question, choices)) {
+            Ok(answer) => answer,
+            Err(err) if err.kind() == io::ErrorKind::UnexpectedEof => return default.unwrap_or(false),
+            Err(err) => input_closed(err),
+        };
+        match (answer.trim().to_lowercase().as_str(), default) {
+            ("y" | "yes", _) => return true,
+            ("n" | "no", _) => return false,
+            ("", Some(default)) => return default,
+            _ => say("Please answer 'y' or 'n'."),
+        }
+    }
+}
+
+// ---
+// ### 5. Interactive Student Entry
+// Collects a student's marks at the prompt, prints their report card, and lets
+// the user correct the entry before moving on.
+// ---
+
+/// How marks are typed in during interactive entry.
+#[derive(Clone, Copy, Default, PartialEq)]
+enum EntryMode {
+    #[default]
+    Total,      // One total for all subjects plus the subject count
+    PerSubject, // Each subject's name and mark, so they can be updated later
+    PerMark,    // The subject count, then one mark per subject, summed into the total
+    Letters,    // The subject count, then a letter grade per subject
+}
+
+/// Prompts until the user types a letter grade from A to D.
+fn get_grade_input(prompt: &str) -> Grade {
+    loop {
+        let input = ask_required(prompt);
+        match Grade::from_letter(&input) {
+            Some(grade) => return grade,
+            None => say("Invalid input. Please enter a grade from A to D."),
+        }
+    }
+}
+
+/// Asks for the A, B and C cutoffs of a one-off grading scale (--custom-scale).
+/// Each cutoff must be a percentage below the one before it; a bad answer
+/// re-prompts for that cutoff only.
+fn get_custom_scale() -> GradingScale {
+    say("Define the grading scale (the lowest average for each grade; anything below C is a D).");
+    let mut cutoffs: Vec<(&'static str, f64)> = Vec::with_capacity(3);
+    for letter in ["A", "B", "C"] {
+        let above = cutoffs.last().copied();
+        loop {
+            let cutoff = get_percent_input(&format!("{} cutoff: ", letter));
+            match above.map_or(Ok(()), |above| check_descending(above, (letter, cutoff))) {
+                Ok(()) => {
+                    cutoffs.push((letter, cutoff));
+                    break;
+                }
+                Err(err) => say(&format!("Invalid cutoff: {}. Please try again.", err)),
+            }
+        }
+    }
+    GradingScale::new(cutoffs[0].1, cutoffs[1].1, cutoffs[2].1).expect("cutoffs were checked as they were entered")
+}
+
+/// Prompts for the number of subjects, then a letter grade for each one.
+/// Each letter is converted to its representative mark, and the rounded sum
+/// becomes the total, returned as (total_marks, num_subjects).
+fn enter_letter_grades(scale: &GradingScale) -> (u32, u32) {
+    let count = get_subject_count();
+    let total: f64 = (1..=count)
+        .map(|number| get_grade_input(&format!("Subject {} of {} grade (A-D): ", number, count)))
+        .map(|grade| grade_to_representative_mark(&grade, scale))
+        .sum();
+    (total.round() as u32, count)
+}
+
+/// Prompts for the number of subjects, then for each subject's mark in turn
+/// ("Subject 3 of 6 mark: "), and returns (total_marks, num_subjects).
+/// Summing here saves the user from adding the marks up by hand.
+fn enter_marks_one_by_one() -> (u32, u32) {
+    let count = get_subject_count();
+    let total = (1..=count)
+        .map(|number| get_mark_input(&format!("Subject {} of {} mark: ", number, count)))
+        .fold(0u32, |total, mark| total.saturating_add(mark));
+    (total, count)
+}
+
+/// Prompts for each subject's name and mark, starting with how many there are.
+/// A mark can also be given out of the paper's own maximum, e.g. "45/50".
+/// With a fixed subject list (from --subjects) only the marks are asked for, in list order.
+fn enter_subjects(names: Option<&[String]>) -> Vec<Subject> {
+    if let Some(names) = names {
+        return names
+            .iter()
+            .map(|name| Subject {
+                name: name.clone(),
+                mark: get_subject_mark_input(name, None),
+                components: Vec::new(),
+            })
+            .collect();
+    }
+    let count = get_subject_count();
+    (1..=count)
+        .map(|number| {
+            let name = get_required_input(&format!("Subject {} name: ", number));
+            let mark = get_subject_mark_input(&name, None);
+            Subject { name, mark, components: Vec::new() }
+        })
+        .collect()
+}
+
+/// Prompts for a student's marks and builds the Student.
+/// In per-subject mode each subject is entered individually, and in per-mark mode
+/// the marks are summed for the user; otherwise, with weights configured, marks
+/// are entered as internal and exam components.
+fn enter_marks(name: String, config: &Config, mode: EntryMode) -> Student {
+    match mode {
+        EntryMode::PerSubject => loop {
+            let mut student = Student::with_subjects(name.clone(), enter_subjects(config.subject_names.as_deref()));
+            match student.dedup_subjects(config.duplicate_subjects) {
+                Ok(merged) => {
+                    if !merged.is_empty() {
+                        say(&format!("Entered more than once, so counted once: {}.", merged.join(", ")));
+                    }
+                    return student;
+                }
+                Err(err) => say(&format!("{}. Please enter the subjects again.", err)),
+            }
+        },
+        EntryMode::PerMark => {
+            let (total_marks, num_subjects) = enter_marks_one_by_one();
+            return Student::new(name, total_marks, num_subjects);
+        }
+        EntryMode::Letters => {
+            let (total_marks, num_subjects) = enter_letter_grades(&grading_scale());
+            return Student::new(name, total_marks, num_subjects);
+        }
+        EntryMode::Total => {}
+    }
+    match config.weights {
+        Some(weights) => {
+            // Each component can be at most what the subjects are worth in total
+            let num_subjects = get_subject_count();
+            let max = num_subjects * MAX_SUBJECT_MARK;
+            let internal = get_u32_in_range("Enter internal marks: ", 0, max);
+            let exam = get_u32_in_range("Enter exam marks: ", 0, max);
+            Student::with_components(name, internal, exam, num_subjects, weights)
+        }
+        None => loop {
+            let total_marks = get_u32_input("Enter total marks: ");
+            let num_subjects = get_subject_count();
+            match check_total_marks(total_marks, num_subjects) {
+                Ok(()) => break Student::new(name, total_marks, num_subjects),
+                Err(err) => say(&format!("{}. Please try again.", err)),
+            }
+        },
+    }
+}
+
+/// Prompts for a student's name. The config can allow a default (such as
+/// "Anonymous") for an empty answer, but never a missing name.
+fn get_student_name(config: &Config) -> String {
+    get_string_input("Enter student's name: ", &config.input.name).unwrap_or_default()
+}
+
+/// Prompts for a student ID until one is entered that no existing student has.
+/// An empty ID (allowed by the `none` policy) means the student has no ID and
+/// can't clash with anyone.
+fn get_student_id(students: &[Student], config: &Config) -> String {
+    loop {
+        let student_id = get_string_input("Enter student ID: ", &config.input.student_id).unwrap_or_default();
+        if !student_id.is_empty() && students.iter().any(|student| student.student_id == student_id) {
+            say(&format!("Student ID '{}' is already taken. Please enter a different ID.", student_id));
+        } else {
+            return student_id;
+        }
+    }
+}
+
+/// Enters one student's marks, prints the report card, and offers corrections.
+/// Students with per-subject marks can also have a single retaken subject updated.
+fn enter_student(student_id: String, name: String, config: &Config, mode: EntryMode) -> Student {
+    let section = get_string_input("Enter section: ", &config.input.section);
+    let mut student = enter_marks(name, config, mode);
+    student.student_id = student_id;
+    student.section = section;
+    student.print_report_card(config);
+
+    // Offer to correct the entry; each prompt defaults to the current value
+    while get_yes_no("Would you like to correct these details?", Some(false)) {
+        edit_student(&mut student);
+        student.print_report_card(config);
+    }
+
+    while !student.subjects.is_empty() && get_yes_no("Update a subject mark after a retake?", Some(false)) {
+        let subject = get_required_input("Enter subject name: ");
+        let new_mark = get_mark_input("Enter new mark: ");
+        match student.update_subject(&subject, new_mark) {
+            Ok(()) => student.print_report_card(config), // Re-display with the recomputed average
+            Err(err) => say(&format!("{}. Please try again.", err)),
+        }
+    }
+    student
+}
+
+/// The field delimiter --quick-add uses unless --quick-delimiter says otherwise.
+const DEFAULT_QUICK_DELIMITER: char = ';';
+
+/// Why a quick-add line couldn't be read as a student.
+#[derive(Debug)]
+enum StudentLineError {
+    WrongFieldCount(usize), // Not exactly a name, a total and a subject count
+    Invalid { field: &'static str, error: ValidationError }, // A field that didn't pass validation
+}
+
+impl std::fmt::Display for StudentLineError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            StudentLineError::WrongFieldCount(found) => {
+                write!(f, "expected 3 fields (name, total marks, subjects) but found {}", found)
+            }
+            StudentLineError::Invalid { field, error } => write!(f, "invalid {}: {}", field, error),
+        }
+    }
+}
+
+/// Reads a whole student from one line such as "John Smith;315;4": the name,
+/// the total marks and the number of subjects, separated by `delimiter`.
+/// Each field is checked as if it had been typed at its own prompt.
+fn parse_student_line(line: &str, delimiter: char) -> Result<Student, StudentLineError> {
+    let fields: Vec<&str> = line.split(delimiter).map(str::trim).collect();
+    let [name, total, count] = fields[..] else {
+        return Err(StudentLineError::WrongFieldCount(fields.len()));
+    };
+    let invalid = |field, error| StudentLineError::Invalid { field, error };
+    let number = |field, value: &str| {
+        parse_u32(value).ok_or_else(|| invalid(field, ValidationError::InvalidNumber(value.to_string())))
+    };
+    check_name(name).map_err(|error| invalid("name", error))?;
+    let total_marks = number("total marks", total)?;
+    let num_subjects = check_subject_count(number("subjects", count)?).map_err(|error| invalid("subjects", error))?;
+    check_total_marks(total_marks, num_subjects).map_err(|error| invalid("total marks", error))?;
+    Ok(Student::new(name.to_string(), total_marks, num_subjects))
+}
+
+/// The class being entered at the keyboard: its students, in entry order, and
+/// the running tally kept in step with them. Every change goes through
+/// `&mut self` and every read through `&self`, so a class can be shared behind
+/// an `Arc<Mutex<Class>>` (as the Ctrl-C handler's copy is) and reported on
+/// from a snapshot while entry carries on.
+#[derive(Clone)]
+struct Class {
+    students: Vec<Student>,
+    tally: RunningTally,
+}
+
+impl Class {
+    /// An empty class. A const fn, so a class can start out in a static.
+    const fn new() -> Self {
+        Self { students: Vec::new(), tally: RunningTally { students: 0, graded: 0, sum_averages: 0.0 } }
+    }
+
+    /// A class of students entered earlier, such as those recovered from an autosave.
+    fn from_students(students: Vec<Student>) -> Self {
+        let tally = RunningTally::recount(&students);
+        Self { students, tally }
+    }
+
+    /// The students entered so far, in entry order.
+    fn students(&self) -> &[Student] {
+        &self.students
+    }
+
+    /// Adds a newly entered student.
+    fn add(&mut self, student: Student) {
+        self.tally.add(&student);
+        self.students.push(student);
+    }
+
+    /// Lets `change` edit the students in place, then recounts the tally, since
+    /// an edited student's old average (or a student marked incomplete) would
+    /// otherwise still be counted.
+    fn edit<R>(&mut self, change: impl FnOnce(&mut [Student]) -> R) -> R {
+        let result = change(&mut self.students);
+        self.tally = RunningTally::recount(&self.students);
+        result
+    }
+
+    /// Removes every student.
+    fn clear(&mut self) {
+        *self = Self::new();
+    }
+
+    /// The progress line for the students entered so far (see RunningTally::progress_line).
+    fn progress_line(&self, format: &ReportFormat) -> String {
+        self.tally.progress_line(format)
+    }
+
+    /// A copy of the students as they are now, consistent however the class
+    /// changes afterwards, for reporting on while entry continues elsewhere.
+    #[cfg_attr(not(feature = "ctrlc"), allow(dead_code))] // Only the Ctrl-C handler shares a class so far
+    fn snapshot(&self) -> Vec<Student> {
+        self.students.clone()
+    }
+
+    /// The students, once entry has finished.
+    fn into_students(self) -> Vec<Student> {
+        self.students
+    }
+}
+
+/// Quick add: reads one student per line (see parse_student_line) until a
+/// blank line or the end of input. A line that can't be read is reported with
+/// the field at fault and can simply be typed again.
+fn quick_add(delimiter: char, class: &mut Class, config: &Config) {
+    say(&format!(
+        "Quick add: type name{0}total marks{0}subjects, one student per line; a blank line finishes.",
+        delimiter
+    ));
+    loop {
+        let line = match ask("+ ") {
+            Ok(line) if !line.is_empty() => line,
+            Ok(_) => break,
+            Err(err) if err.kind() == io::ErrorKind::UnexpectedEof => break, // The end of input finishes too
+            Err(err) => input_closed(err),
+        };
+        match parse_student_line(&line, delimiter) {
+            Ok(student) => {
+                say(&student.format_compact(&config.format));
+                class.add(student);
+                say(&class.progress_line(&config.format));
+                remember_session(class);
+            }
+            Err(err) => say(&format!("{}. Please try again.", err)),
+        }
+    }
+}
+
+/// Runs the interactive session and returns every student entered.
+/// Students recovered from an unfinished session's autosave come first.
+/// With a roster, only marks are asked for each listed name (skipping any
+/// already recovered); with quick add, whole students are typed one per line;
+/// otherwise a single student is entered from scratch, unless some were recovered.
+fn run_interactive(roster: Option<Vec<String>>, config: &Config, mode: EntryMode) -> Vec<Student> {
+    say("Welcome to the Student Report Card Generator!");
+
+    let from_roster = roster.is_some();
+    let mut class = Class::from_students(recover_autosave());
+    match (roster, config.quick_add) {
+        (_, Some(delimiter)) => quick_add(delimiter, &mut class, config),
+        // Roster mode: the names are known, so only ask for each student's marks
+        (Some(names), None) => {
+            let count = names.len();
+            // How many of each name were entered before the session was cut short.
+            // Names aren't unique, so each recovered student skips one roster entry.
+            let mut recovered: BTreeMap<String, usize> = BTreeMap::new();
+            for student in class.students() {
+                *recovered.entry(student.name.clone()).or_insert(0) += 1;
+            }
+            for (index, name) in names.into_iter().enumerate() {
+                if let Some(left) = recovered.get_mut(&name).filter(|left| **left > 0) {
+                    *left -= 1;
+                    continue;
+                }
+                clear_between_steps();
+                say(&format!("\nStudent {} of {}: {}", index + 1, count, name));
+                let student_id = get_student_id(class.students(), config);
+                let student = enter_student(student_id, name, config, mode);
+                class.add(student);
+                say(&class.progress_line(&config.format));
+                remember_session(&class);
+            }
+        }
+        // Get student details using our helper functions
+        (None, None) if class.students().is_empty() => {
+            clear_between_steps();
+            let student_id = get_student_id(&[], config);
+            let name = get_student_name(config);
+            let student = enter_student(student_id, name, config, mode);
+            class.add(student); // No progress line yet: a single student isn't a batch
+            remember_session(&class);
+        }
+        (None, None) => say(&class.progress_line(&config.format)), // Carry on from the recovered students
+    }
+
+    if config.commands {
+        run_commands(&mut class, config, mode);
+    } else {
+        run_menu(&mut class, config, mode);
+    }
+
+    let students = class.into_students();
+
+    // A summary only makes sense for a class, not a single student
+    if (from_roster || students.len() > 1) && !config.no_summary {
+        print_summary(&students, config);
+    }
+
+    say("Thank you for using the Student Report Card Generator!");
+    students
+}
+
+/// The actions offered once the initial students have been entered.
+enum MenuAction {
+    AddStudent,
+    EditStudent,
+    OverrideGrade,
+    MarksNeeded,
+    DiscardAll,
+    Finish,
+}
+
+/// The menu with its prompt, shown in one piece so a closed stdout is caught by ask.
+const MENU: &str = "
+What would you like to do next?
+  1. Add another student
+  2. Edit a student's details
+  3. Set or clear a grade override
+  4. What grade do I need?
+  5. Discard all students
+  6. Finish
+Enter choice: ";
+
+/// Shows the menu and waits for a valid choice. End of input counts as Finish.
+fn get_menu_action() -> MenuAction {
+    loop {
+        match ask(MENU) {
+            Ok(choice) => match choice.as_str() {
+                "1" => return MenuAction::AddStudent,
+                "2" => return MenuAction::EditStudent,
+                "3" => return MenuAction::OverrideGrade,
+                "4" => return MenuAction::MarksNeeded,
+                "5" => return MenuAction::DiscardAll,
+                "6" => return MenuAction::Finish,
+                _ => say("Invalid choice. Please enter a number from 1 to 6."),
+            },
+            Err(err) if err.kind() == io::ErrorKind::UnexpectedEof => return MenuAction::Finish, // Nothing more to read
+            Err(err) => input_closed(err),
+        }
+    }
+}
+
+/// Finds a student by ID, or failing that by name (ignoring case). When several
+/// students share the name, the first one entered is returned.
+fn find_student_mut<'a>(students: &'a mut [Student], key: &str) -> Option<&'a mut Student> {
+    let index = student_index(students, key)?;
+    Some(&mut students[index])
+}
+
+/// Looks up a student by ID or name, like find_student_mut, for reading only.
+fn find_student<'a>(students: &'a [Student], key: &str) -> Option<&'a Student> {
+    student_index(students, key).map(|index| &students[index])
+}
+
+/// Where the student with ID `key` is, or failing that the first one named
+/// `key` (ignoring case).
+fn student_index(students: &[Student], key: &str) -> Option<usize> {
+    students
+        .iter()
+        .position(|student| student.student_id == key)
+        .or_else(|| students.iter().position(|student| student.name.eq_ignore_ascii_case(key)))
+}
+
+/// Asks for a student by name and sets or clears a manual grade override.
+fn override_grade_action(students: &mut [Student], config: &Config) {
+    let key = get_required_input("Enter the student's ID or name: ");
+    let Some(student) = find_student_mut(students, &key) else {
+        say(&format!("No student with ID or name '{}'.", key));
+        return;
+    };
+
+    loop {
+        let input = get_required_input("Enter override grade (A-D), 'I' for incomplete, or 'clear' to remove it: ");
+        if input.eq_ignore_ascii_case("clear") {
+            student.override_grade = None;
+            student.incomplete = false;
+            break;
+        }
+        if input.eq_ignore_ascii_case("i") {
+            student.override_grade = None; // Incomplete replaces any letter set by hand
+            student.incomplete = true;
+            break;
+        }
+        match Grade::from_letter(&input) {
+            Some(grade) => {
+                student.override_grade = Some(grade);
+                student.incomplete = false; // A letter set by hand means the course is finished
+                break;
+            }
+            None => say("Invalid input. Please enter a grade from A to D, 'I', or 'clear'."),
+        }
+    }
+    student.print_report_card(config);
+}
+
+/// Asks for a student by name and says how many more marks would lift their
+/// computed grade to the next band, then offers to work out the average needed
+/// for a target GPA next term.
+fn marks_needed_action(students: &[Student], format: &ReportFormat) {
+    let key = get_required_input("Enter the student's ID or name: ");
+    let Some(student) = find_student(students, &key) else {
+        say(&format!("No student with ID or name '{}'.", key));
+        return;
+    };
+    print_marks_needed(student);
+
+    let Some(answer) = get_string_input("Target GPA for next term: ", &EmptyInput::None) else {
+        return; // Left blank: not planning ahead
+    };
+    match answer.parse::<f64>() {
+        Ok(target) => match required_average_for_gpa(target, &grading_scale()) {
+            Ok(average) => say(&format!(
+                "A GPA of {} needs an average of at least {} next term.",
+                target,
+                format.average(average)
+            )),
+            Err(err) => say(&format!("{}.", err)),
+        },
+        Err(_) => say(&format!("'{}' is not a valid GPA.", answer)),
+    }
+}
+
+/// Says how many more marks would lift the student's computed grade to the next band.
+fn print_marks_needed(student: &Student) {
+    let current = student.assign_grade();
+    let Some(next) = current.next_up() else {
+        match current {
+            Grade::Invalid => say(&format!("{} has no subjects, so there is no grade to improve.", student.name)),
+            _ => say(&format!("{} already has the top grade.", student.name)),
+        }
+        return;
+    };
+    match Student::marks_needed_for(next, student, &grading_scale()) {
+        Some(marks) => say(&format!(
+            "{} needs {} more total marks across {} subjects to reach grade {}.",
+            student.name,
+            marks,
+            student.num_subjects,
+            next.as_str()
+        )),
+        None => say(&format!(
+            "{} can't reach grade {} with {} subjects, even with full marks.",
+            student.name,
+            next.as_str(),
+            student.num_subjects
+        )),
+    }
+}
+
+/// Returned when a target GPA is higher than any grade's points.
+#[derive(Debug)]
+struct GpaOutOfReach(f64);
+
+impl std::fmt::Display for GpaOutOfReach {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "GPA {} is out of reach; the most is {:.1}", self.0, MAX_GRADE_POINTS)
+    }
+}
+
+/// The grade points an A is worth, the most any average can earn.
+const MAX_GRADE_POINTS: f64 = 4.0;
+
+/// The lowest average whose grade band on `scale` earns at least `target_gpa`
+/// grade points, e.g. 75 for a GPA of 3 on the default scale. Any target up to
+/// a D's points needs no particular average, so 0 is returned.
+fn required_average_for_gpa(target_gpa: f64, scale: &GradingScale) -> Result<f64, GpaOutOfReach> {
+    // Each grade with the lowest average that earns it, weakest first
+    let bands = [(Grade::D, 0.0), (Grade::C, scale.c), (Grade::B, scale.b), (Grade::A, scale.a)];
+    bands
+        .into_iter()
+        .find(|(grade, _)| grade.points().is_some_and(|points| points >= target_gpa))
+        .map(|(_, cutoff)| cutoff)
+        .ok_or(GpaOutOfReach(target_gpa))
+}
+
+/// Runs the menu loop until the user chooses to finish.
+fn run_menu(class: &mut Class, config: &Config, mode: EntryMode) {
+    loop {
+        remember_session(class); // Picks up the last student added or overridden
+        match get_menu_action() {
+            MenuAction::AddStudent => {
+                clear_between_steps();
+                let student_id = get_student_id(class.students(), config);
+                let name = get_student_name(config);
+                let student = enter_student(student_id, name, config, mode);
+                class.add(student);
+                say(&class.progress_line(&config.format));
+            }
+            MenuAction::EditStudent => class.edit(|students| edit_student_action(students, config)),
+            MenuAction::OverrideGrade => class.edit(|students| override_grade_action(students, config)),
+            MenuAction::MarksNeeded => marks_needed_action(class.students(), &config.format),
+            MenuAction::DiscardAll => discard_all_action(class),
+            MenuAction::Finish => return,
+        }
+    }
+}
+
+/// A command typed at the `>` prompt of the command interpreter (--commands).
+enum Command {
+    Add,            // add: enter another student
+    List,           // list: one line per student
+    Show(String),   // show <ID or name>: print that student's report card
+    Stats,          // stats: print the class summary
+    DiscardAll,     // discard all: throw away every student entered so far
+    Export(String), // export <file>: write the class in the format named by the extension
+    Quit,           // quit: finish the session
+}
+
+/// What the command interpreter prints for `help` or anything it doesn't understand.
+const COMMAND_HELP: &str = "Commands: add, list, show <ID or name>, stats, export <file>, discard all, quit";
+
+/// Parses a line typed at the command prompt. The command word is case-insensitive;
+/// None for an unknown command or a missing argument.
+fn parse_command(line: &str) -> Option<Command> {
+    let line = line.trim();
+    let (word, argument) = line.split_once(char::is_whitespace).unwrap_or((line, ""));
+    let argument = argument.trim();
+    match (word.to_lowercase().as_str(), argument.is_empty()) {
+        ("add", true) => Some(Command::Add),
+        ("list", true) => Some(Command::List),
+        ("show", false) => Some(Command::Show(argument.to_string())),
+        ("stats", true) => Some(Command::Stats),
+        ("discard", false) if argument.eq_ignore_ascii_case("all") => Some(Command::DiscardAll),
+        ("export", false) => Some(Command::Export(argument.to_string())),
+        ("quit" | "exit", true) => Some(Command::Quit),
+        _ => None,
+    }
+}
+
+/// Runs the command interpreter that --commands offers in place of the numbered
+/// menu, until `quit` or the end of input.
+fn run_commands(class: &mut Class, config: &Config, mode: EntryMode) {
+    say(&format!("\n{}", COMMAND_HELP));
+    loop {
+        remember_session(class);
+        let line = match ask("> ") {
+            Ok(line) => line,
+            Err(err) if err.kind() == io::ErrorKind::UnexpectedEof => return, // Nothing more to read
+            Err(err) => input_closed(err),
+        };
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        match parse_command(&line) {
+            Some(Command::Add) => {
+                clear_between_steps();
+                let student_id = get_student_id(class.students(), config);
+                let name = get_student_name(config);
+                let student = enter_student(student_id, name, config, mode);
+                class.add(student);
+                say(&class.progress_line(&config.format));
+            }
+            Some(Command::List) => {
+                for student in class.students() {
+                    say(&student.format_compact(&config.format));
+                }
+            }
+            Some(Command::Show(key)) => match find_student(class.students(), &key) {
+                Some(student) => student.print_report_card(config),
+                None => say(&format!("No student with ID or name '{}'.", key)),
+            },
+            Some(Command::Stats) => print_summary(class.students(), config),
+            Some(Command::DiscardAll) => discard_all_action(class),
+            Some(Command::Export(path)) => export_from_command(&path, class.students(), config),
+            Some(Command::Quit) => return,
+            None => say(COMMAND_HELP),
+        }
+    }
+}
+
+/// Handles `export <file>`: writes the whole class in the format the extension
+/// names, reporting the outcome rather than ending the session on failure.
+fn export_from_command(path: &str, students: &[Student], config: &Config) {
+    let format = match format_for_path(path) {
+        Ok(format) => format,
+        Err(err) => {
+            say(&format!("Can't export to {}: {}.", path, err));
+            return;
+        }
+    };
+    let selected: Vec<&Student> = students.iter().collect();
+    let noun = if selected.len() == 1 { "record" } else { "records" };
+    match write_export(format, path, &selected, CsvExportOptions::default(), None, config) {
+        Ok(()) => say(&format!("Wrote {} {} to {} ({}).", format_thousands(selected.len()), noun, path, format.name())),
+        Err(err) => say(&format!("Could not {}: {}", format.write_action(), err)),
+    }
+}
+
+/// Throws away every student entered so far, e.g. after starting on the wrong
+/// class, once the user has confirmed how many will be lost. Nothing is saved.
+fn discard_all_action(class: &mut Class) {
+    let students = class.students().len();
+    if students == 0 {
+        say("There are no students to discard.");
+        return;
+    }
+    let count = format!("{} {}", format_thousands(students), if students == 1 { "student" } else { "students" });
+    if get_yes_no(&format!("Discard all {}? This can't be undone.", count), None) {
+        class.clear();
+        say(&format!("Discarded {}.", count));
+    }
+}
+
+/// Asks which student to edit, lets the user correct their details, and
+/// reprints the report card so the grade reflects the new marks.
+fn edit_student_action(students: &mut [Student], config: &Config) {
+    let key = get_required_input("Enter the student's ID or name: ");
+    let Some(student) = find_student_mut(students, &key) else {
+        say(&format!("No student with ID or name '{}'.", key));
+        return;
+    };
+    edit_student(student);
+    student.print_report_card(config); // Grades are computed on demand, so this one is current
+}
+
+/// Lets the user correct a student's details after seeing the report card.
+/// Every prompt shows the current value, so pressing Enter keeps it unchanged.
+fn edit_student(student: &mut Student) {
+    student.name = get_string_input_default("Enter student's name: ", &student.name);
+    if !student.subjects.is_empty() {
+        for subject in &mut student.subjects {
+            subject.name = get_string_input_default("Subject name: ", &subject.name);
+            subject.mark = get_subject_mark_input(&subject.name, Some(subject.mark));
+        }
+        student.recompute_totals();
+        return; // The subject count follows from the list
+    }
+    let get_count = |current| get_u32_in_range_default("Enter number of subjects: ", current, 1, MAX_SUBJECTS);
+    if student.weights.is_some() {
+        student.num_subjects = get_count(student.num_subjects);
+        let max = student.num_subjects * MAX_SUBJECT_MARK; // As when the student was first entered
+        student.internal = get_u32_in_range_default("Enter internal marks: ", student.internal, 0, max);
+        student.exam = get_u32_in_range_default("Enter exam marks: ", student.exam, 0, max);
+        student.total_marks = student.internal + student.exam; // Keep the raw sum in step
+        return;
+    }
+    // Checked together, as when the student was first entered
+    loop {
+        let total_marks = get_u32_input_default("Enter total marks: ", student.total_marks);
+        let num_subjects = get_count(student.num_subjects);
+        match check_total_marks(total_marks, num_subjects) {
+            Ok(()) => {
+                student.total_marks = total_marks;
+                student.num_subjects = num_subjects;
+                return;
+            }
+            Err(err) => say(&format!("{}. Please try again.", err)),
+        }
+    }
+}
+
+/// Reads a class roster: one student name per line. Blank lines are skipped
+/// and surrounding whitespace is trimmed from each name.
+fn read_roster(reader: impl BufRead) -> io::Result<Vec<String>> {
+    let mut names = Vec::new();
+    for line in reader.lines() {
+        let line = line?;
+        let name = line.trim();
+        if !name.is_empty() {
+            names.push(name.to_string());
+        }
+    }
+    Ok(names)
+}
+
+/// Where an interrupted session is saved, as a JSON export that `--input` reads
+/// back with every field intact (see to_json).
+#[cfg(feature = "ctrlc")]
+const SESSION_FILE: &str = "interrupted_session.json";
+
+/// Set by the first Ctrl-C; a second one quits without saving.
+#[cfg(feature = "ctrlc")]
+static INTERRUPTED: AtomicBool = AtomicBool::new(false);
+
+/// The class entered so far, shared with the Ctrl-C handler for saving if the
+/// session is interrupted.
+#[cfg(feature = "ctrlc")]
+static SESSION: std::sync::Mutex<Class> = std::sync::Mutex::new(Class::new());
+
+/// The directory and name of the autosave file, which is kept in the user's
+/// state directory (see autosave_path) rather than wherever the program was started.
+const AUTOSAVE_DIR: &str = "student_report";
+const AUTOSAVE_FILE: &str = "autosave.json";
+
+/// Set once an autosave has failed, so the warning isn't repeated for every student.
+static AUTOSAVE_FAILED: AtomicBool = AtomicBool::new(false);
+
+/// Where interactive entry is autosaved, so a crash or a closed terminal
+/// doesn't lose it: AUTOSAVE_FILE in AUTOSAVE_DIR under the user's own state
+/// directory ($XDG_STATE_HOME, else ~/.local/state, or %LOCALAPPDATA% on
+/// Windows). Unlike the shared temp directory, other users can't plant a file
+/// or a link there. None, and no autosave, when there is no such directory.
+/// Left behind only when a session ends abnormally.
+fn autosave_path() -> Option<PathBuf> {
+    let set = |name| env::var_os(name).filter(|value| !value.is_empty()).map(PathBuf::from);
+    let state = set("XDG_STATE_HOME")
+        .or_else(|| set("HOME").map(|home| home.join(".local").join("state")))
+        .or_else(|| set("LOCALAPPDATA"))?;
+    Some(state.join(AUTOSAVE_DIR).join(AUTOSAVE_FILE))
+}
+
+/// Creates the autosave's directory if needed, readable by its owner only.
+fn create_autosave_dir(dir: &Path) -> io::Result<()> {
+    let mut builder = fs::DirBuilder::new();
+    builder.recursive(true);
+    #[cfg(unix)]
+    std::os::unix::fs::DirBuilderExt::mode(&mut builder, 0o700);
+    builder.create(dir)
+}
+
+/// Records the students entered so far: autosaves them (see autosave_path) and,
+/// with the `ctrlc` feature, keeps them for saving on Ctrl-C. An empty class has
+/// nothing worth recovering, so instead any earlier autosave is removed (as
+/// after discarding every student).
+fn remember_session(class: &Class) {
+    #[cfg(feature = "ctrlc")]
+    if let Ok(mut session) = SESSION.lock() {
+        session.clone_from(class);
+    }
+    let students = class.students();
+    let Some(path) = autosave_path() else {
+        return; // Nowhere private to keep it
+    };
+    if students.is_empty() {
+        remove_autosave();
+        return;
+    }
+    let saved = path.parent().map_or(Ok(()), create_autosave_dir).and_then(|()| autosave(students, &path));
+    if let Err(err) = saved
+        && !AUTOSAVE_FAILED.swap(true, Ordering::Relaxed)
+    {
+        eprintln!("Warning: could not autosave to {} ({}); entry continues unsaved", path.display(), err);
+    }
+}
+
+/// Writes the students to `path` as a JSON export, which holds every field
+/// entry can set (see to_json). The file is written under another name first
+/// and then renamed, so a crash mid-write can't leave a half-written autosave
+/// in place of the last good one.
+fn autosave(students: &[Student], path: &Path) -> io::Result<()> {
+    let selected: Vec<&Student> = students.iter().collect();
+    let mut partial = path.as_os_str().to_owned();
+    partial.push(".tmp");
+    fs::write(&partial, to_json(&selected, &BTreeMap::new(), &ReportFormat::default()))?;
+    fs::rename(&partial, path)
+}
+
+/// Offers to recover the students from an autosave an earlier session left
+/// behind, returning them if the user agrees. A declined or unreadable
+/// autosave is deleted so it isn't offered again.
+fn recover_autosave() -> Vec<Student> {
+    let Some(path) = autosave_path().filter(|path| path.exists()) else {
+        return Vec::new();
+    };
+    let read = fs::read_to_string(&path)
+        .map_err(JsonImportError::from)
+        .and_then(|text| read_students_from_json(&text, false, DuplicateSubjects::default()));
+    let students = match read {
+        Ok(students) => students,
+        Err(err) => {
+            eprintln!("Warning: ignoring the unreadable autosave {} ({})", path.display(), err);
+            Vec::new()
+        }
+    };
+    let noun = if students.len() == 1 { "student" } else { "students" };
+    let question =
+        format!("An unfinished session left {} {} in {}. Recover them?", students.len(), noun, path.display());
+    if !students.is_empty() && get_yes_no(&question, Some(true)) {
+        return students;
+    }
+    remove_autosave();
+    Vec::new()
+}
+
+/// Deletes the autosave once the session it protected has ended properly.
+fn remove_autosave() {
+    let Some(path) = autosave_path() else {
+        return;
+    };
+    match fs::remove_file(&path) {
+        Err(err) if err.kind() != io::ErrorKind::NotFound => {
+            eprintln!("Warning: could not remove {} ({})", path.display(), err);
+        }
+        _ => {}
+    }
+}
+
+/// A snapshot of the remembered class, taken under the lock so it is consistent
+/// however entry carries on afterwards. A lock poisoned by a panic mid-update
+/// still holds the last complete class, so that is returned rather than nothing.
+#[cfg(feature = "ctrlc")]
+fn session_snapshot() -> Vec<Student> {
+    match SESSION.lock() {
+        Ok(session) => session.snapshot(),
+        Err(poisoned) => poisoned.into_inner().snapshot(),
+    }
+}
+
+/// Installs the Ctrl-C handler for interactive entry. The first Ctrl-C offers
+/// to save the students entered so far, which happens on the next Enter; a
+/// second Ctrl-C quits at once, as Ctrl-C normally would.
+#[cfg(feature = "ctrlc")]
+fn install_interrupt_handler() {
+    let installed = ctrlc::set_handler(|| {
+        if INTERRUPTED.swap(true, Ordering::Relaxed) {
+            process::exit(130); // The conventional status for a program stopped by Ctrl-C
+        }
+        eprintln!("\nInterrupted. Press Enter to save the students entered so far and exit, or Ctrl-C again to quit without saving.");
+    });
+    if let Err(err) = installed {
+        eprintln!("Warning: Ctrl-C will quit without saving ({})", err);
+    }
+}
+
+/// Writes the remembered students to SESSION_FILE and exits. A student only
+/// half entered when Ctrl-C was pressed isn't included.
+#[cfg(feature = "ctrlc")]
+fn save_session_and_exit() -> ! {
+    let students = session_snapshot();
+    if students.is_empty() {
+        eprintln!("No students had been entered; nothing to save.");
+        process::exit(0);
+    }
+    // Written like an autosave, so every field survives and a failed write can't leave half a file
+    match autosave(&students, Path::new(SESSION_FILE)) {
+        Ok(()) => {
+            let noun = if students.len() == 1 { "student" } else { "students" };
+            eprintln!(
+                "Saved {} {} to {}; continue with --input {}",
+                students.len(),
+                noun,
+                SESSION_FILE,
+                SESSION_FILE
+            );
+            remove_autosave(); // The saved session supersedes it
+            process::exit(0);
+        }
+        Err(source) => {
+            report_error(ErrorFormat::Human, &FileError { what: "save interrupted session", source });
+            process::exit(1);
+        }
+    }
+}
+
+// ---
+// ### 6. Colored Output
+// ANSI escape codes highlight results in the terminal. Setting the NO_COLOR
+// environment variable (to any non-empty value) turns colors off, and they are
+// also left out when stdout is redirected to a file or pipe, so saved output stays
+// plain text. `--force-color` turns them back on, e.g. when piping into `less -R`.
+// `--clear` uses another escape sequence to clear the terminal between students.
+// ---
+
+const GREEN: &str = "\x1b[32m";
+const RED: &str = "\x1b[31m";
+const YELLOW: &str = "\x1b[33m";
+const RESET: &str = "\x1b[0m";
+
+/// Set by `--clear`: clear the screen before each student is entered.
+static CLEAR_SCREEN: AtomicBool = AtomicBool::new(false);
+
+/// Clears the terminal and moves the cursor to the top-left corner.
+fn clear_screen(writer: &mut impl Write) -> io::Result<()> {
+    write!(writer, "\x1b[2J\x1b[H")?;
+    writer.flush()
+}
+
+/// Clears the screen between interactive steps when `--clear` was given.
+/// Nothing happens when stdout isn't a terminal, so piped output stays clean.
+fn clear_between_steps() {
+    let mut stdout = io::stdout();
+    if CLEAR_SCREEN.load(Ordering::Relaxed) && stdout.is_terminal() {
+        // Clearing is cosmetic, so a failed write is ignored
+        let _ = clear_screen(&mut stdout);
+    }
+}
+
+/// The class pass rate (in percent) at or above which the banner is shown in green.
+const PASS_RATE_THRESHOLD: f64 = 75.0;
+
+/// Set by `--force-color`; wins over both NO_COLOR and the terminal check.
+static FORCE_COLOR: AtomicBool = AtomicBool::new(false);
+
+/// Returns true when stdout is a terminal and the user hasn't opted out of
+/// colors via NO_COLOR, or always when `--force-color` was given.
+fn colors_enabled() -> bool {
+    if FORCE_COLOR.load(Ordering::Relaxed) {
+        return true;
+    }
+    io::stdout().is_terminal() && env::var_os("NO_COLOR").is_none_or(|value| value.is_empty())
+}
+
+/// Wraps text in the given ANSI color code, or returns it unchanged when colors are off.
+fn colorize(text: &str, color: &str) -> String {
+    colorize_if(text, color, colors_enabled())
+}
+
+/// Like colorize, but for output that isn't stdout (e.g. a summary saved in an
+/// export), where the caller decides whether colors belong.
+fn colorize_if(text: &str, color: &str, colors: bool) -> String {
+    if colors {
+        format!("{}{}{}", color, text, RESET)
+    } else {
+        text.to_string()
+    }
+}
+
+/// The percentage of graded students who passed outright (supplementary results
+/// don't count as passes). Students without a grade are left out; returns None
+/// if nobody was graded.
+fn pass_rate(students: &[Student], config: &Config) -> Option<f64> {
+    let outcomes: Vec<Outcome> = students.iter().map(|student| student.outcome(config)).collect();
+    let graded = outcomes.iter().filter(|outcome| **outcome != Outcome::NotGraded).count();
+    let passed = outcomes.iter().filter(|outcome| **outcome == Outcome::Pass).count();
+    safe_divide(passed as f64 * 100.0, graded as u32)
+}
+
+/// Lists each student whose subjects don't match the curriculum. Students
+/// entered with only a total have no subject names to check and are skipped.
+fn write_curriculum_issues(
+    writer: &mut impl Write,
+    students: &[Student],
+    curriculum: &[String],
+    format: &ReportFormat,
+) -> io::Result<()> {
+    let mut any = false;
+    for student in students.iter().filter(|student| !student.subjects.is_empty()) {
+        let issues = student.validate_against_curriculum(curriculum);
+        if issues.is_empty() {
+            continue;
+        }
+        if !any {
+            writeln!(writer, "Curriculum issues:")?;
+            any = true;
+        }
+        let issues: Vec<String> = issues.iter().map(|issue| issue.to_string()).collect();
+        writeln!(writer, "  {:<13}: {}", format.display_name(&student.name), issues.join(", "))?;
+    }
+    Ok(())
+}
+
+/// Prints a class-level banner such as "CLASS PASS RATE: 82%", green when the
+/// rate meets PASS_RATE_THRESHOLD and red otherwise (when `colors` is on).
+fn print_class_banner(rate: f64, writer: &mut impl Write, colors: bool) -> io::Result<()> {
+    let color = if rate >= PASS_RATE_THRESHOLD { GREEN } else { RED };
+    let banner = format!("CLASS PASS RATE: {:.0}%", rate);
+    writeln!(writer, "===========================")?;
+    writeln!(writer, "{}", colorize_if(&banner, color, colors))?;
+    writeln!(writer, "===========================")
+}
+
+// ---
+// ### 7. Class Summary
+// After a batch of report cards, a short summary describes the class as a whole.
+// ---
+
+/// How many of the lowest-scoring subjects the class summary lists.
+const WEAKEST_SUBJECTS_SHOWN: usize = 3;
+
+/// Averages closer than this to the class mean count as "at" the mean.
+const MEAN_EPSILON: f64 = 1e-6;
+
+/// How many grades a class tally counts: A to D and N/A, in Grade::index order.
+const GRADE_SLOTS: usize = 5;
+
+/// Headline figures for a whole class.
+struct ClassSummary {
+    student_count: usize,
+    grade_counts: [usize; GRADE_SLOTS], // Number of students with each grade: A, B, C, D, N/A
+    incomplete: usize,        // Students graded I, who are in no grade count and no average
+    distinctions: usize,      // Students whose final grade is a distinction
+    mean_average: Option<f64>, // Mean of the students' averages (None if nobody has one)
+    above_mean: usize,        // Students whose average is above the class mean
+    at_mean: usize,           // Students whose average equals the mean (within MEAN_EPSILON)
+    below_mean: usize,        // Students whose average is below the class mean
+    grand_total: u64,         // Every complete student's total marks added together (0 for an empty class)
+    overall_average: f64,     // Weighted totals over all subjects taken, so larger course loads count more (0 if none)
+}
+
+/// A running count and class average kept up to date as students are entered,
+/// so the progress line doesn't need to rescan the whole class each time.
+#[derive(Clone, Default)]
+struct RunningTally {
+    students: usize,   // Everyone entered so far
+    graded: u32,       // Students with an average (at least one subject)
+    sum_averages: f64, // Sum of those averages
+}
+
+impl RunningTally {
+    /// Tallies the students from scratch, for when one already counted has
+    /// been edited and the running figures no longer match.
+    fn recount(students: &[Student]) -> Self {
+        let mut tally = Self::default();
+        for student in students {
+            tally.add(student);
+        }
+        tally
+    }
+
+    /// Adds one newly entered student to the tally.
+    fn add(&mut self, student: &Student) {
+        self.students += 1;
+        if let Some(average) = student.class_average() {
+            self.graded += 1;
+            self.sum_averages += average;
+        }
+    }
+
+    /// The progress line, e.g. "Entered 12 students so far, running class average 71.40."
+    fn progress_line(&self, format: &ReportFormat) -> String {
+        let average = safe_divide(self.sum_averages, self.graded)
+            .map(|mean| format.average(mean))
+            .unwrap_or_else(|| "N/A".to_string());
+        let noun = if self.students == 1 { "student" } else { "students" };
+        format!("Entered {} {} so far, running class average {}.", self.students, noun, average)
+    }
+}
+
+/// How many of the grades fall in each grade slot. Incomplete grades aren't
+/// counted, as class_summary counts incomplete students separately.
+fn count_grades(grades: impl IntoIterator<Item = Grade>) -> [usize; GRADE_SLOTS] {
+    let mut counts = [0; GRADE_SLOTS];
+    for grade in grades.into_iter().filter(|grade| *grade != Grade::Incomplete) {
+        counts[grade.index()] += 1;
+    }
+    counts
+}
+
+/// Tallies the class: how many students there are, how many got each grade,
+/// and how the students' averages spread around the class mean.
+/// Accepts any group of students, such as a whole class or one section of it.
+fn class_summary<'a>(students: impl IntoIterator<Item = &'a Student>) -> ClassSummary {
+    let students: Vec<&Student> = students.into_iter().collect();
+    let grade_counts = count_grades(students.iter().map(|student| student.final_grade()));
+
+    // Students without an average (no subjects, or incomplete) are left out of the mean comparison
+    let averages: Vec<f64> = students.iter().filter_map(|student| student.class_average()).collect();
+    let mean_average = safe_divide(averages.iter().sum(), averages.len() as u32);
+    let (mut above_mean, mut at_mean, mut below_mean) = (0, 0, 0);
+    if let Some(mean) = mean_average {
+        for average in &averages {
+            if (average - mean).abs() < MEAN_EPSILON {
+                at_mean += 1;
+            } else if *average > mean {
+                above_mean += 1;
+            } else {
+                below_mean += 1;
+            }
+        }
+    }
+
+    // Summed per subject rather than averaging the averages, so each mark counts once
+    let complete: Vec<&&Student> = students.iter().filter(|student| !student.incomplete).collect();
+    let grand_total: u64 = complete.iter().map(|student| u64::from(student.total_marks)).sum();
+    let total_subjects: u64 = complete.iter().map(|student| u64::from(student.num_subjects)).sum();
+    // Weighted totals, so internal/exam students count as their report cards do
+    let weighted_total: f64 = complete.iter().map(|student| student.weighted_total()).sum();
+    let overall_average = if total_subjects == 0 { 0.0 } else { weighted_total / total_subjects as f64 };
+
+    ClassSummary {
+        student_count: students.len(),
+        grade_counts,
+        incomplete: students.iter().filter(|student| student.incomplete).count(),
+        distinctions: students.iter().filter(|student| student.final_grade().is_distinction()).count(),
+        mean_average,
+        above_mean,
+        at_mean,
+        below_mean,
+        grand_total,
+        overall_average,
+    }
+}
+
+/// Each grade's share of the class in percent, in the order A, B, C, D, N/A,
+/// rounded to one decimal place. Rounding uses the largest-remainder method so
+/// the shares always add up to exactly 100 (or are all 0 for an empty class).
+/// Incomplete students have no grade yet and are left out.
+fn grade_percentages(summary: &ClassSummary) -> [f64; GRADE_SLOTS] {
+    let total: usize = summary.grade_counts.iter().sum();
+    if total == 0 {
+        return [0.0; GRADE_SLOTS];
+    }
+    // Work in tenths of a percent: round every share down, then hand the
+    // leftover tenths to the grades that lost the most in rounding
+    let exact = summary.grade_counts.map(|count| count * 1000);
+    let mut tenths = exact.map(|value| value / total);
+    let leftover = 1000 - tenths.iter().sum::<usize>();
+    let mut by_remainder: Vec<usize> = (0..GRADE_SLOTS).collect();
+    by_remainder.sort_by_key(|&slot| std::cmp::Reverse(exact[slot] % total));
+    for &slot in by_remainder.iter().take(leftover) {
+        tenths[slot] += 1;
+    }
+    tenths.map(|value| value as f64 / 10.0)
+}
+
+/// A one-line recap of a run, e.g.
+/// "Generated 42 report cards, 5 A / 12 B / 20 C / 5 D, 2 N/A".
+fn format_run_summary(summary: &ClassSummary) -> String {
+    let [a, b, c, d, none] = summary.grade_counts;
+    let cards = if summary.student_count == 1 { "report card" } else { "report cards" };
+    let mut line = format!(
+        "Generated {} {}, {} A / {} B / {} C / {} D, {} {}",
+        format_thousands(summary.student_count),
+        cards, a, b, c, d, none, no_grade_label()
+    );
+    if summary.incomplete > 0 {
+        line.push_str(&format!(", {} incomplete", summary.incomplete));
+    }
+    line
+}
+
+/// Returns the `n` subjects with the lowest mean mark across the class, lowest first.
+/// The means come from subject_class_averages.
+fn weakest_subjects(students: &[Student], n: usize) -> Vec<(String, f64)> {
+    let mut means: Vec<(String, f64)> = subject_class_averages(students).into_iter().collect();
+    means.sort_by(|a, b| a.1.total_cmp(&b.1)); // Stable sort keeps ties in name order
+    means.truncate(n);
+    means
+}
+
+/// The class's mean mark in every subject, by subject name. Each subject is
+/// averaged over the students who sat it, with names matched ignoring case
+/// and shown as first seen. A subject everyone was absent from has no mean and
+/// is left out.
+fn subject_class_averages(students: &[Student]) -> BTreeMap<String, f64> {
+    // Lower-cased name -> (name as first seen, sum of marks, number of students)
+    let mut totals: BTreeMap<String, (String, u64, u32)> = BTreeMap::new();
+    // Incomplete students' marks so far would drag their subjects down unfairly
+    for subject in students.iter().filter(|student| !student.incomplete).flat_map(|student| &student.subjects) {
+        let Some(mark) = subject.mark.score() else {
+            continue; // Absences would pull the subject's average down like a zero
+        };
+        let entry = totals
+            .entry(subject.name.to_lowercase())
+            .or_insert_with(|| (subject.name.clone(), 0, 0));
+        entry.1 += mark as u64;
+        entry.2 += 1;
+    }
+    totals.into_values().map(|(name, sum, count)| (name, sum as f64 / count as f64)).collect()
+}
+
+/// A table of how many students had each grade last term and this term, and
+/// the change, e.g. "  B        4      6     +2". Only students found in both
+/// terms (by ID) are counted, so the shift isn't just students joining or
+/// leaving; how many were left out is noted below the table. Students whose
+/// grade moved are listed after it, with an up arrow in green for a better grade
+/// and a down arrow in red for a worse one; without `colors` the lines start
+/// with `+` and `-` instead, so the meaning survives redirection. Last term's
+/// students are graded on the scale their file was graded on.
+fn format_band_comparison(prev: &ClassFile, curr: &[Student], colors: bool) -> String {
+    let matched: Vec<(&Student, &Student)> = curr
+        .iter()
+        .filter(|student| !student.student_id.is_empty())
+        .filter_map(|student| {
+            let earlier = prev.students.iter().find(|earlier| earlier.student_id == student.student_id)?;
+            Some((earlier, student))
+        })
+        .collect();
+    let last = count_grades(matched.iter().map(|(earlier, _)| earlier.final_grade_on(&prev.scale)));
+    let this = count_grades(matched.iter().map(|(_, student)| student.final_grade()));
+
+    let mut table = String::from("Compared with last term:\n");
+    table.push_str(&format!("  {:<6} {:>6} {:>6} {:>7}\n", "Grade", "Last", "This", "Change"));
+    for grade in (0..GRADE_SLOTS).filter_map(Grade::from_index) {
+        let (before, after) = (last[grade.index()], this[grade.index()]);
+        let change = after as i64 - before as i64;
+        table.push_str(&format!("  {:<6} {:>6} {:>6} {:>+7}\n", grade.as_str(), before, after, change));
+    }
+    let unmatched = curr.len() - matched.len();
+    if unmatched > 0 {
+        let noun = if unmatched == 1 { "student" } else { "students" };
+        table.push_str(&format!("  ({} {} not found in last term's file left out)\n", unmatched, noun));
+    }
+
+    let changes: Vec<String> = matched
+        .iter()
+        .filter_map(|(earlier, student)| {
+            let (before, after) = (earlier.final_grade_on(&prev.scale), student.final_grade());
+            let rise = after.points()? - before.points()?; // Ungraded either term isn't a move
+            let change = format!("{}: {} -> {}", student.name, before.as_str(), after.as_str());
+            let (arrow, sign, color) = match rise {
+                rise if rise > 0.0 => ("↑", "+", GREEN),
+                rise if rise < 0.0 => ("↓", "-", RED),
+                _ => return None,
+            };
+            if colors {
+                Some(colorize_if(&format!("{} {}", arrow, change), color, true))
+            } else {
+                Some(format!("{} {}", sign, change))
+            }
+        })
+        .collect();
+    if !changes.is_empty() {
+        table.push_str("  Grade changes:\n");
+        for change in changes {
+            table.push_str(&format!("    {}\n", change));
+        }
+    }
+    table
+}
+
+/// The label used for students who weren't given a section.
+const UNASSIGNED_SECTION: &str = "Unassigned";
+
+/// Summarises each section of the class separately, keyed by section name in
+/// alphabetical order. Students without a section are grouped under "Unassigned".
+fn summary_by_section(students: &[Student]) -> BTreeMap<String, ClassSummary> {
+    let mut sections: BTreeMap<String, Vec<&Student>> = BTreeMap::new();
+    for student in students {
+        let section = student.section.as_deref().unwrap_or(UNASSIGNED_SECTION);
+        sections.entry(section.to_string()).or_default().push(student);
+    }
+    sections
+        .into_iter()
+        .map(|(section, members)| (section, class_summary(members)))
+        .collect()
+}
+
+/// Writes the class summary followed by the pass-rate banner, with colors
+/// only when `colors` is on.
+fn write_summary(writer: &mut impl Write, students: &[Student], config: &Config, colors: bool) -> io::Result<()> {
+    let format = &config.format;
+    let summary = class_summary(students);
+    writeln!(writer, "--- Class Summary ---")?;
+    writeln!(writer, "{:<15}: {}", "Students", summary.student_count)?;
+    writeln!(writer, "{:<15}: {}", "Distinctions", summary.distinctions)?;
+    if summary.incomplete > 0 {
+        writeln!(writer, "{:<15}: {}", "Incomplete", summary.incomplete)?;
+    }
+    writeln!(writer, "{:<15}: {}", "Grand Total", summary.grand_total)?;
+    writeln!(writer, "{:<15}: {}", "Overall Avg", format.average(summary.overall_average))?;
+    if let Some(mean) = summary.mean_average {
+        writeln!(writer, "{:<15}: {}", "Class Mean", format.average(mean))?;
+        writeln!(writer, "{:<15}: {}", "Above Mean", summary.above_mean)?;
+        writeln!(writer, "{:<15}: {}", "At Mean", summary.at_mean)?;
+        writeln!(writer, "{:<15}: {}", "Below Mean", summary.below_mean)?;
+    }
+
+    if summary.grade_counts.iter().sum::<usize>() > 0 {
+        writeln!(writer, "Grade distribution:")?;
+        let percentages = grade_percentages(&summary);
+        for grade in (0..GRADE_SLOTS).filter_map(Grade::from_index) {
+            let slot = grade.index();
+            writeln!(writer, "  {:<13}: {} ({:.1}%)", grade.as_str(), summary.grade_counts[slot], percentages[slot])?;
+        }
+    }
+
+    if let Some(previous) = &config.previous {
+        write!(writer, "{}", format_band_comparison(previous, students, colors))?;
+    }
+
+    // Only worth breaking down when the roster actually uses sections
+    if students.iter().any(|student| student.section.is_some()) {
+        writeln!(writer, "By section:")?;
+        for (section, summary) in summary_by_section(students) {
+            let mean = summary.mean_average.map(|mean| format.average(mean)).unwrap_or_else(|| "N/A".to_string());
+            let noun = if summary.student_count == 1 { "student" } else { "students" };
+            let [a, b, c, d, none] = summary.grade_counts;
+            writeln!(
+                writer,
+                "  {:<13}: {} {}, mean {}, {} A / {} B / {} C / {} D, {} {}",
+                section, summary.student_count, noun, mean, a, b, c, d, none, no_grade_label()
+            )?;
+        }
+    }
+
+    for (n, heading, listed) in [
+        (config.top, "Top students (highest average):", top_n(students, config.top)),
+        (config.bottom, "Bottom students (lowest average):", bottom_n(students, config.bottom)),
+    ] {
+        if n == 0 || listed.is_empty() {
+            continue;
+        }
+        writeln!(writer, "{}", heading)?;
+        for student in listed {
+            if format.grades_only {
+                writeln!(writer, "  {}", format.display_name(&student.name))?; // The order alone, no scores
+                continue;
+            }
+            let average = student.class_average().map(|average| format.average(average)).unwrap_or_default();
+            writeln!(writer, "  {:<13}: {}", format.display_name(&student.name), average)?;
+        }
+    }
+
+    let subject_averages = subject_class_averages(students);
+    if !subject_averages.is_empty() {
+        writeln!(writer, "Subject averages:")?;
+        for (name, mean) in subject_averages {
+            writeln!(writer, "  {:<13}: {}", name, format.average(mean))?;
+        }
+    }
+    let weakest = weakest_subjects(students, WEAKEST_SUBJECTS_SHOWN);
+    if !weakest.is_empty() {
+        writeln!(writer, "Weakest subjects (lowest class average):")?;
+        for (name, mean) in weakest {
+            writeln!(writer, "  {:<13}: {}", name, format.average(mean))?;
+        }
+    }
+    if let Some(scale) = &config.compare_scale {
+        write_grade_movement(writer, students, scale)?;
+    }
+    if let Some(credits) = &config.credits {
+        write_class_gpa(writer, &class_weighted_gpa(students, credits), format)?;
+    }
+    if let Some(benchmark) = config.benchmark {
+        write_benchmark(writer, &compare_to_benchmark(students, benchmark), format, colors)?;
+    }
+    if let Some(curriculum) = &config.curriculum {
+        write_curriculum_issues(writer, students, curriculum, format)?;
+    }
+    writeln!(writer)?;
+
+    if let Some(rate) = pass_rate(students, config) {
+        print_class_banner(rate, writer, colors)?;
+    }
+    Ok(())
+}
+
+/// An explanation of the grades and markers used in the reports, following the
+/// scale and rules in force, for readers seeing a report for the first time.
+fn format_legend(config: &Config) -> String {
+    let scale = grading_scale();
+    let mut legend = String::from("--- Legend ---\n");
+    for grade in [Grade::A, Grade::B, Grade::C, Grade::D] {
+        let band = scale.band(grade).expect("letter grades have a band");
+        let band = if grade == Grade::A { format!("average of {}", band) } else { band };
+        legend.push_str(&format!("{:<15}: {}\n", grade.as_str(), band));
+    }
+    legend.push_str(&format!("{:<15}: incomplete, left out of class averages\n", "I"));
+    legend.push_str(&format!("{:<15}: no subjects, so no grade\n", no_grade_label()));
+    legend.push_str(&format!("{:<15}: a grade of A, B or C\n", Outcome::Pass.as_str()));
+    if let Some(pass_mark) = config.subject_pass_mark {
+        legend.push_str(&format!("{:<15}: a pass with a subject below {}\n", Outcome::Supplementary.as_str(), pass_mark));
+        if !config.subject_pass_marks.is_empty() {
+            let marks: Vec<String> =
+                config.subject_pass_marks.iter().map(|(subject, mark)| format!("{} {}", subject, mark)).collect();
+            legend.push_str(&format!("{:<15}  (or its own pass mark: {})\n", "", marks.join(", ")));
+        }
+    }
+    if let Some(core) = &config.core {
+        legend.push_str(&format!(
+            "{:<15}: a pass with a core subject ({}) below {}\n",
+            Outcome::CoreFail.as_str(),
+            core.subjects.join(", "),
+            core.pass_mark
+        ));
+    }
+    legend.push_str("A subject's own grade is shown after its mark, e.g. \"82 (B)\".\n");
+    legend.push_str("\"(manually adjusted)\" marks a grade set by hand instead of from the marks.\n");
+    legend
+}
+
+/// Prints the class summary to the console.
+fn print_summary(students: &[Student], config: &Config) {
+    // A failed write to stdout has nowhere to be reported, so it is ignored
+    let _ = write_summary(&mut io::stdout(), students, config, colors_enabled());
+}
+
+/// The class summary as plain text, without colors, for saving in an export.
+fn summary_text(students: &[Student], config: &Config) -> String {
+    let mut text = Vec::new();
+    write_summary(&mut text, students, config, false).expect("writing to memory cannot fail");
+    String::from_utf8_lossy(&text).into_owned()
+}
+
+/// The students with an average, best or weakest first. Ties keep class order.
+fn ranked_by_average(students: &[Student], best_first: bool) -> Vec<&Student> {
+    let mut ranked: Vec<(&Student, f64)> = students
+        .iter()
+        .filter_map(|student| student.class_average().map(|average| (student, average)))
+        .collect();
+    // Stable, so tied students stay in class order either way
+    if best_first {
+        ranked.sort_by(|a, b| b.1.total_cmp(&a.1));
+    } else {
+        ranked.sort_by(|a, b| a.1.total_cmp(&b.1));
+    }
+    ranked.into_iter().map(|(student, _)| student).collect()
+}
+
+/// The `n` students with the highest averages, best first, for recognition.
+/// Students without an average are left out; a large `n` returns everyone.
+fn top_n(students: &[Student], n: usize) -> Vec<&Student> {
+    ranked_by_average(students, true).into_iter().take(n).collect()
+}
+
+/// The `n` students with the lowest averages, weakest first, for intervention.
+/// Students without an average are left out; a large `n` returns everyone.
+fn bottom_n(students: &[Student], n: usize) -> Vec<&Student> {
+    ranked_by_average(students, false).into_iter().take(n).collect()
+}
+
+/// The students to announce at an awards ceremony, grouped by their rank in the
+/// whole class and ordered from the lowest rank up, so the top student comes last.
+/// Students tied on a rank share a group, in name order. Only students whose
+/// final grade is one of `grades` are kept (all when it's empty); students
+/// without an average have no rank and are left out.
+fn announcement_order<'a>(students: &'a [Student], grades: &[Grade]) -> Vec<(usize, Vec<&'a Student>)> {
+    let class: Vec<&Student> = students.iter().collect();
+    let mut ranked: Vec<(usize, &Student)> = class
+        .iter()
+        .zip(rank_students(&class))
+        .filter_map(|(student, rank)| rank.map(|rank| (rank, *student)))
+        .filter(|(_, student)| grades.is_empty() || grades.contains(&student.final_grade()))
+        .collect();
+    ranked.sort_by(|a, b| b.0.cmp(&a.0).then_with(|| a.1.name.to_lowercase().cmp(&b.1.name.to_lowercase())));
+    ranked
+        .chunk_by(|a, b| a.0 == b.0)
+        .map(|group| (group[0].0, group.iter().map(|(_, student)| *student).collect()))
+        .collect()
+}
+
+/// The line announcing a rank before its report cards, e.g. "*** Rank 2 (3 tied) ***".
+fn announcement_heading(rank: usize, count: usize) -> String {
+    if count > 1 {
+        format!("*** Rank {} ({} tied) ***", rank, count)
+    } else {
+        format!("*** Rank {} ***", rank)
+    }
+}
+
+/// The students whose final grade is one of `grades`, in their original order.
+/// An empty list keeps everyone, so callers can pass the filter through unconditionally.
+fn filter_by_grade<'a>(students: &'a [Student], grades: &[Grade]) -> Vec<&'a Student> {
+    students
+        .iter()
+        .filter(|student| grades.is_empty() || grades.contains(&student.final_grade()))
+        .collect()
+}
+
+/// Parses a comma-separated list of letter grades such as "C,D".
+fn parse_grade_list(value: &str) -> Option<Vec<Grade>> {
+    value.split(',').map(Grade::from_letter).collect()
+}
+
+/// How many students' grades went up, down, or stayed put between two gradings.
+#[derive(Default)]
+struct Movement {
+    up: usize,
+    down: usize,
+    unchanged: usize, // Includes students without a grade band (incomplete or ungraded) either time
+}
+
+/// Compares each student's grade before and after a re-grade, pairing the two
+/// lists by position.
+fn grade_movement(before: &[Grade], after: &[Grade]) -> Movement {
+    let mut movement = Movement::default();
+    for (before, after) in before.iter().zip(after) {
+        match (before.points(), after.points()) {
+            (Some(old), Some(new)) if new > old => movement.up += 1,
+            (Some(old), Some(new)) if new < old => movement.down += 1,
+            _ => movement.unchanged += 1,
+        }
+    }
+    movement
+}
+
+/// Writes e.g. "Under 85/70/55 : 12 up, 3 down, 27 unchanged", comparing the
+/// grades the class earned with those it would earn on `scale`.
+fn write_grade_movement(writer: &mut impl Write, students: &[Student], scale: &GradingScale) -> io::Result<()> {
+    let before: Vec<Grade> = students.iter().map(|student| student.assign_grade()).collect();
+    let after: Vec<Grade> = students.iter().map(|student| student.grade_on(scale)).collect();
+    let movement = grade_movement(&before, &after);
+    writeln!(
+        writer,
+        "{:<15}: {} up, {} down, {} unchanged",
+        format!("Under {}/{}/{}", scale.a, scale.b, scale.c),
+        movement.up,
+        movement.down,
+        movement.unchanged
+    )
+}
+
+/// Credit hours for a subject the `credits` setting doesn't list.
+const DEFAULT_SUBJECT_CREDITS: u32 = 1;
+
+/// The institution-level GPA of a class.
+struct ClassGpa {
+    gpa: Option<f64>, // Total credit-weighted grade points over total credits (None if nobody has credits)
+    excluded: usize,  // Students left out for having no credits, e.g. only a total was entered
+}
+
+/// The class GPA: every student's credit-weighted grade points added up and
+/// divided by the class's total credits, so heavier subjects count for more.
+/// Students with zero credits are excluded (and counted); incomplete students
+/// are left out as they are from the class mean.
+fn class_weighted_gpa(students: &[Student], credits: &BTreeMap<String, u32>) -> ClassGpa {
+    let scale = grading_scale();
+    let mut points = 0.0;
+    let mut total_credits = 0;
+    let mut excluded = 0;
+    for student in students.iter().filter(|student| !student.incomplete) {
+        match student.credit_points(credits, &scale) {
+            (_, 0) => excluded += 1,
+            (student_points, student_credits) => {
+                points += student_points;
+                total_credits += student_credits;
+            }
+        }
+    }
+    ClassGpa { gpa: safe_divide(points, total_credits), excluded }
+}
+
+/// Writes the class GPA line, noting any students it leaves out.
+fn write_class_gpa(writer: &mut impl Write, gpa: &ClassGpa, format: &ReportFormat) -> io::Result<()> {
+    let value = gpa.gpa.map(|gpa| format.average(gpa)).unwrap_or_else(|| "N/A".to_string());
+    match gpa.excluded {
+        0 => writeln!(writer, "{:<15}: {}", "Class GPA", value),
+        1 => writeln!(writer, "{:<15}: {} (1 student without credits excluded)", "Class GPA", value),
+        excluded => writeln!(writer, "{:<15}: {} ({} students without credits excluded)", "Class GPA", value, excluded),
+    }
+}
+
+/// One term on a student's transcript: their average, if any, and final grade.
+#[derive(Clone, Copy)]
+struct TermResult {
+    average: Option<f64>,
+    grade: Grade,
+}
+
+/// A student's record across several terms, for --transcript.
+struct Transcript {
+    name: String,                   // As given in the latest term the student appears in
+    terms: Vec<Option<TermResult>>, // One entry per term, in order; None for a term the student missed
+}
+
+impl Transcript {
+    /// The mean grade points over the terms that earned any, or None when no
+    /// term did. Every term counts the same, whatever its subjects or credits.
+    fn cumulative_gpa(&self) -> Option<f64> {
+        let points: Vec<f64> = self.terms.iter().flatten().filter_map(|term| term.grade.points()).collect();
+        safe_divide(points.iter().sum(), points.len() as u32)
+    }
+}
+
+/// Builds a transcript for every student in `terms` (oldest first), keyed by
+/// student ID. A student missing from a term has a gap there rather than being
+/// dropped. Records without an ID can't be matched across terms and are skipped.
+fn build_transcript(terms: &[ClassFile]) -> BTreeMap<String, Transcript> {
+    let mut transcripts: BTreeMap<String, Transcript> = BTreeMap::new();
+    for (index, term) in terms.iter().enumerate() {
+        for student in term.students.iter().filter(|student| !student.student_id.is_empty()) {
+            let transcript = transcripts
+                .entry(student.student_id.clone())
+                .or_insert_with(|| Transcript { name: String::new(), terms: vec![None; terms.len()] });
+            transcript.name = student.name.clone();
+            let result = TermResult { average: student.class_average(), grade: student.final_grade_on(&term.scale) };
+            transcript.terms[index] = Some(result);
+        }
+    }
+    transcripts
+}
+
+/// Writes one student's transcript in the same layout as a report card: a
+/// line per term with its average and grade, then the cumulative GPA.
+fn write_transcript(
+    writer: &mut impl Write,
+    student_id: &str,
+    transcript: &Transcript,
+    format: &ReportFormat,
+) -> io::Result<()> {
+    writeln!(writer, "\n--- Transcript ---")?;
+    writeln!(writer, "{:<15}: {}", "Name", format.display_name(&transcript.name))?;
+    writeln!(writer, "{:<15}: {}", "Student ID", student_id)?;
+    for (number, term) in transcript.terms.iter().enumerate() {
+        let result = match term {
+            Some(TermResult { average: Some(average), grade }) => {
+                format!("{} ({})", format.average(*average), grade.as_str())
+            }
+            Some(TermResult { average: None, grade }) => grade.as_str().to_string(),
+            None => "not enrolled".to_string(),
+        };
+        writeln!(writer, "{:<15}: {}", format!("Term {}", number + 1), result)?;
+    }
+    let gpa = transcript.cumulative_gpa().map(|gpa| format.average(gpa)).unwrap_or_else(|| "N/A".to_string());
+    writeln!(writer, "{:<15}: {}", "Cumulative GPA", gpa)?;
+    writeln!(writer, "---------------------------\n")
+}
+
+/// How a class compares with an outside benchmark average, e.g. a district's.
+struct BenchmarkReport {
+    benchmark: f64,
+    class_mean: Option<f64>, // The mean of the students' averages (None if nobody has one)
+    below: usize,            // Students whose average is below the benchmark
+}
+
+impl BenchmarkReport {
+    /// How far the class mean is above (positive) or below (negative) the benchmark.
+    fn delta(&self) -> Option<f64> {
+        self.class_mean.map(|mean| mean - self.benchmark)
+    }
+}
+
+/// Compares the class mean and each student's average with a benchmark.
+fn compare_to_benchmark(students: &[Student], benchmark: f64) -> BenchmarkReport {
+    let averages: Vec<f64> = students.iter().filter_map(|student| student.class_average()).collect();
+    BenchmarkReport {
+        benchmark,
+        class_mean: safe_divide(averages.iter().sum(), averages.len() as u32),
+        below: averages.iter().filter(|average| **average < benchmark).count(),
+    }
+}
+
+/// Writes e.g. "Class mean 73.20 is +3.20 vs benchmark 70", with the difference
+/// in green when the class is at or above the benchmark and red when below.
+fn write_benchmark(writer: &mut impl Write, report: &BenchmarkReport, format: &ReportFormat, colors: bool) -> io::Result<()> {
+    if let (Some(mean), Some(delta)) = (report.class_mean, report.delta()) {
+        let sign = if delta >= 0.0 { "+" } else { "" }; // Negative numbers bring their own sign
+        let color = if delta >= 0.0 { GREEN } else { RED };
+        writeln!(
+            writer,
+            "Class mean {} is {} vs benchmark {}",
+            format.average(mean),
+            colorize_if(&format!("{}{}", sign, format.average(delta)), color, colors),
+            report.benchmark
+        )?;
+    }
+    writeln!(writer, "{:<15}: {}", "Below Benchmark", report.below)
+}
+
+// ---
+// ### 8. CSV Import
+// Students can also be loaded in bulk from a CSV file with the header
+// `name,total_marks,num_subjects` followed by one student per line. A header
+// starting with `student_id` adds the ID as an extra first column, and one
+// ending with `section` adds the student's section as an extra last column.
+// Lines starting with '#' are comments; one of the form `# scale: 85,70,55`
+// sets the A, B and C cutoffs the file's students are graded with. Fields may
+// be quoted as in RFC 4180 (and as spreadsheets export them), so a quoted field
+// can hold commas, doubled quotes ("") and even line breaks.
+// ---
+
+/// The number of columns every CSV data row must have, without the optional columns.
+const CSV_COLUMNS: usize = 3;
+
+/// Which optional columns a CSV file has, as announced by its header row.
+#[derive(Clone, Copy, Default)]
+struct CsvLayout {
+    has_id: bool,      // First column is `student_id`
+    has_section: bool, // Last column is `section` (blank for no section)
+}
+
+impl CsvLayout {
+    /// Reads the layout from the header's fields, as split by split_csv_record,
+    /// so a quoted column name may contain commas.
+    fn from_header(columns: &[String]) -> Self {
+        Self {
+            has_id: columns.first().is_some_and(|column| column == "student_id"),
+            has_section: columns.last().is_some_and(|column| column == "section"),
+        }
+    }
+
+    /// How many fields every data row must have.
+    fn columns(&self) -> usize {
+        CSV_COLUMNS + usize::from(self.has_id) + usize::from(self.has_section)
+    }
+}
+
+/// Everything that can go wrong while reading a CSV file.
+/// Line numbers are 1-based and count the header line.
+#[derive(Debug)]
+enum CsvError {
+    Io(io::Error), // The file could not be read
+    WrongColumnCount { line: usize, expected: usize, found: usize }, // A row has too few or too many fields
+    Invalid { line: usize, field: &'static str, error: ValidationError }, // A field failed validation
+    InvalidScale { line: usize, value: String, error: ScaleError }, // A `# scale:` comment that isn't a valid scale
+    UnclosedQuote { line: usize }, // A quoted field still open at the end of the file
+}
+
+impl std::fmt::Display for CsvError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            CsvError::Io(err) => write!(f, "could not read CSV: {}", err),
+            CsvError::WrongColumnCount { line, expected, found } => write!(
+                f,
+                "line {}: expected {} columns but found {}",
+                line, expected, found
+            ),
+            CsvError::Invalid { line, field, error } => {
+                write!(f, "line {}: invalid {}: {}", line, field, error)
+            }
+            CsvError::InvalidScale { line, value, error } => {
+                write!(f, "line {}: invalid scale '{}': {}", line, value, error)
+            }
+            CsvError::UnclosedQuote { line } => write!(f, "line {}: a quoted field is never closed", line),
+        }
+    }
+}
+
+impl From<io::Error> for CsvError {
+    fn from(err: io::Error) -> Self {
+        CsvError::Io(err)
+    }
+}
+
+/// Parses one numeric CSV field, reporting the line and field name on failure.
+fn parse_csv_u32(value: &str, line: usize, field: &'static str) -> Result<u32, CsvError> {
+    value.parse::<u32>().map_err(|_| CsvError::Invalid {
+        line,
+        field,
+        error: ValidationError::InvalidNumber(value.to_string()),
+    })
+}
+
+/// The result of a CSV import: the students read, plus the rows that were
+/// skipped when importing with `continue_on_error`.
+struct CsvImport {
+    students: Vec<Student>,
+    skipped: Vec<CsvError>,       // One error per bad row, in file order
+    scale: Option<GradingScale>,  // From a `# scale:` comment, when the file has one
+}
+
+/// Parses the cutoffs of a `# scale: 85,70,55` comment (the text after the
+/// colon): three marks from 0 to 100 for A, B and C, each below the last.
+fn parse_scale(value: &str) -> Result<GradingScale, ScaleError> {
+    let cutoffs: Vec<f64> = value
+        .split(',')
+        .map(|cutoff| cutoff.trim().parse::<f64>())
+        .collect::<Result<_, _>>()
+        .map_err(|_| ScaleError::Malformed)?;
+    let [a, b, c] = cutoffs[..] else {
+        return Err(ScaleError::Malformed);
+    };
+    GradingScale::new(a, b, c)
+}
+
+/// Splits one CSV record into its fields, following RFC 4180: a field in
+/// double quotes may contain commas, line breaks and doubled quotes (""), and is
+/// kept exactly as written. Unquoted fields are trimmed. Returns None when a
+/// quoted field isn't closed, meaning the record continues on the next line.
+fn split_csv_record(record: &str) -> Option<Vec<String>> {
+    let mut fields = Vec::new();
+    let mut field = String::new();
+    let mut quoted = false; // The current field started with a quote
+    let mut in_quotes = false; // Inside the quotes, where commas don't split
+    let mut chars = record.chars().peekable();
+    while let Some(c) = chars.next() {
+        if in_quotes {
+            match c {
+                '"' if chars.peek() == Some(&'"') => {
+                    field.push('"'); // A doubled quote stands for one
+                    chars.next();
+                }
+                '"' => in_quotes = false,
+                _ => field.push(c),
+            }
+            continue;
+        }
+        match c {
+            '"' if !quoted && field.trim().is_empty() => {
+                field.clear(); // Spaces before the opening quote aren't part of the field
+                quoted = true;
+                in_quotes = true;
+            }
+            ',' => {
+                let done = std::mem::take(&mut field);
+                fields.push(if quoted { done } else { done.trim().to_string() });
+                quoted = false;
+            }
+            c if quoted && c.is_whitespace() => {} // Spaces after the closing quote
+            _ => field.push(c),
+        }
+    }
+    if in_quotes {
+        return None;
+    }
+    fields.push(if quoted { field } else { field.trim().to_string() });
+    Some(fields)
+}
+
+/// Reads students from CSV text. The first line that isn't a comment is treated
+/// as a header and skipped, as are blank lines. Comment lines start with '#';
+/// a `# scale: A,B,C` comment sets the import's grading scale. Each data row must
+/// have exactly `CSV_COLUMNS` fields, plus a leading ID field when the header's
+/// first column is `student_id`. A quoted field can span several lines; errors
+/// give the line its row starts on. A bad row stops the import with its error,
+/// unless `continue_on_error` is set, in which case the row is skipped and its
+/// error collected. I/O errors and an unclosed quote always stop it.
+fn read_students_from_csv(reader: impl BufRead, continue_on_error: bool) -> Result<CsvImport, CsvError> {
+    let mut import = CsvImport { students: Vec::new(), skipped: Vec::new(), scale: None };
+    let mut layout = None;
+    let mut pending: Option<(usize, String)> = None; // A row with a quoted field still open: (first line, text so far)
+
+    for (index, line) in reader.lines().enumerate() {
+        let line_no = index + 1; // Human-friendly, 1-based line numbers
+        let line = line?;
+        let (line_no, record) = match pending.take() {
+            Some((start, text)) => (start, text + "\n" + &line), // The line break is part of the quoted field
+            None => {
+                if let Some(comment) = line.trim_start().strip_prefix('#') {
+                    // Other comments (such as the rank note an export starts with) are skipped
+                    if let Some(value) = comment.trim().strip_prefix("scale:") {
+                        let scale = parse_scale(value).map_err(|error| CsvError::InvalidScale {
+                            line: line_no,
+                            value: value.trim().to_string(),
+                            error,
+                        })?;
+                        import.scale = Some(scale);
+                    }
+                    continue;
+                }
+                (line_no, line)
+            }
+        };
+        let Some(fields) = split_csv_record(&record) else {
+            pending = Some((line_no, record)); // Read on until the quote closes
+            continue;
+        };
+        let Some(layout) = layout else {
+            layout = Some(CsvLayout::from_header(&fields));
+            continue; // The header only tells us which optional columns are present
+        };
+        if record.trim().is_empty() {
+            continue; // Skip blank lines
+        }
+
+        match parse_csv_row(fields, line_no, layout) {
+            Ok(student) => import.students.push(student),
+            Err(err) if continue_on_error => import.skipped.push(err),
+            Err(err) => return Err(err),
+        }
+    }
+
+    match pending {
+        Some((line, _)) => Err(CsvError::UnclosedQuote { line }),
+        None => Ok(import),
+    }
+}
+
+/// Parses one CSV data row into a Student.
+fn parse_csv_row(mut fields: Vec<String>, line_no: usize, layout: CsvLayout) -> Result<Student, CsvError> {
+    let expected = layout.columns();
+    if fields.len() != expected {
+        return Err(CsvError::WrongColumnCount {
+            line: line_no,
+            expected,
+            found: fields.len(),
+        });
+    }
+
+    let section = if layout.has_section { fields.pop().filter(|section| !section.is_empty()) } else { None };
+    let (student_id, fields) = if layout.has_id { (fields[0].as_str(), &fields[1..]) } else { ("", &fields[..]) };
+    let total_marks = parse_csv_u32(&fields[1], line_no, "total_marks")?;
+    let num_subjects = parse_csv_u32(&fields[2], line_no, "num_subjects")?;
+    // A row with no subjects is allowed (the student is graded N/A), so only the name and total are checked
+    let invalid = |field, error| CsvError::Invalid { line: line_no, field, error };
+    check_name(&fields[0]).map_err(|error| invalid("name", error))?;
+    check_total_marks(total_marks, num_subjects).map_err(|error| invalid("total_marks", error))?;
+    let mut student = Student::new(fields[0].to_string(), total_marks, num_subjects);
+    student.student_id = student_id.to_string();
+    student.section = section;
+    Ok(student)
+}
+
+// ---
+// ### 9. CSV Export
+// `--export-csv <file>` writes one row per student with the computed average and
+// grade. `--with-rank` adds rank and percentile columns, which need the whole
+// class, so they are computed in a pass before any row is written.
+// ---
+
+/// Quotes a CSV field when it contains a comma, quote or line break,
+/// doubling any embedded quotes.
+fn csv_field(text: &str) -> String {
+    if text.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", text.replace('"', "\"\""))
+    } else {
+        text.to_string()
+    }
+}
+
+/// Ranks students by average, highest first, using competition ranking:
+/// tied students share a rank and the next rank skips ahead ("1224").
+/// Students without an average get no rank.
+fn rank_students(students: &[&Student]) -> Vec<Option<usize>> {
+    let averages: Vec<Option<f64>> = students.iter().map(|student| student.class_average()).collect();
+    averages
+        .iter()
+        .map(|average| {
+            let average = (*average)?;
+            let higher = averages.iter().flatten().filter(|other| **other > average).count();
+            Some(higher + 1)
+        })
+        .collect()
+}
+
+/// The percentage of graded students whose average is strictly below each
+/// student's average. Students without an average get no percentile.
+fn percentiles(students: &[&Student]) -> Vec<Option<f64>> {
+    let averages: Vec<Option<f64>> = students.iter().map(|student| student.class_average()).collect();
+    let graded = averages.iter().flatten().count() as u32;
+    averages
+        .iter()
+        .map(|average| {
+            let average = (*average)?;
+            let below = averages.iter().flatten().filter(|other| **other < average).count();
+            safe_divide(below as f64 * 100.0, graded)
+        })
+        .collect()
+}
+
+/// How the CSV export is written.
+#[derive(Clone, Copy, Default)]
+struct CsvExportOptions {
+    with_rank: bool,    // Add rank and percentile columns
+    append: bool,       // Add rows to an existing file instead of replacing it
+    language: Language, // Set by --lang: the language of the column headers
+}
+
+/// The language exported column headers are written in. Only the headers are
+/// translated; values such as averages and grade letters are the same in every language.
+#[derive(Clone, Copy, Default, PartialEq)]
+enum Language {
+    #[default]
+    English,
+    Spanish,
+}
+
+impl Language {
+    /// Parses a language code as given to --lang ("en" or "es").
+    fn parse(code: &str) -> Option<Self> {
+        match code.trim().to_lowercase().as_str() {
+            "en" => Some(Language::English),
+            "es" => Some(Language::Spanish),
+            _ => None,
+        }
+    }
+
+    /// The CSV column header for `column`, which is given by its English name.
+    fn csv_column(self, column: &'static str) -> &'static str {
+        if self == Language::English {
+            return column;
+        }
+        match column {
+            "student_id" => "id_estudiante",
+            "name" => "nombre",
+            "total_marks" => "total_puntos",
+            "num_subjects" => "num_asignaturas",
+            "average" => "promedio",
+            "percentage" => "porcentaje",
+            "grade" => "calificacion",
+            "computed_grade" => "calificacion_calculada",
+            "rank" => "puesto",
+            "percentile" => "percentil",
+            other => other,
+        }
+    }
+}
+
+/// The comment row that documents the ranking method in ranked exports.
+const CSV_RANK_COMMENT: &str = "# rank: competition ranking by average (ties share a rank, e.g. 1,2,2,4); \
+                                percentile: % of graded students with a lower average";
+
+/// The CSV column header row for an export with or without rank columns,
+/// or the minimal grades-only layout, in the given language.
+fn csv_export_header(with_rank: bool, grades_only: bool, language: Language) -> String {
+    let columns: &[&'static str] = if grades_only {
+        &["student_id", "name", "grade"]
+    } else if with_rank {
+        &[
+            "student_id", "name", "total_marks", "num_subjects", "average", "percentage", "grade", "computed_grade",
+            "rank", "percentile",
+        ]
+    } else {
+        &["student_id", "name", "total_marks", "num_subjects", "average", "percentage", "grade", "computed_grade"]
+    };
+    let columns: Vec<&str> = columns.iter().map(|&column| language.csv_column(column)).collect();
+    columns.join(",")
+}
+
+/// Writes students as CSV. Averages use the configured precision and are left empty
+/// when a student has no subjects; `percentage` is the marks out of the per-subject
+/// maximums in `max_marks`, at the same precision. `grade` is the final grade (including any manual
+/// override) and `computed_grade` the one derived from the average. With `with_rank`,
+/// a leading comment row explains the ranking method and `rank` and `percentile`
+/// columns are appended. The header rows are skipped when `include_header` is false,
+/// and are otherwise written in `language`.
+fn write_csv(
+    students: &[&Student],
+    writer: &mut impl Write,
+    with_rank: bool,
+    include_header: bool,
+    language: Language,
+    max_marks: &BTreeMap<String, u32>,
+    format: &ReportFormat,
+) -> io::Result<()> {
+    // Rank and percentile depend on the whole class, so work them out up front
+    let ranking = if with_rank {
+        Some((rank_students(students), percentiles(students)))
+    } else {
+        None
+    };
+
+    if include_header {
+        if with_rank {
+            writeln!(writer, "{}", CSV_RANK_COMMENT)?;
+        }
+        writeln!(writer, "{}", csv_export_header(with_rank, format.grades_only, language))?;
+    }
+
+    for (index, student) in students.iter().enumerate() {
+        if format.grades_only {
+            writeln!(
+                writer,
+                "{},{},{}",
+                csv_field(&student.student_id),
+                csv_field(&student.name),
+                csv_field(student.final_grade().as_str())
+            )?;
+            continue;
+        }
+        let average = student
+            .calculate_average()
+            .map(|average| format.average(average))
+            .unwrap_or_default();
+        write!(
+            writer,
+            "{},{},{},{},{},{},{},{}",
+            csv_field(&student.student_id),
+            csv_field(&student.name),
+            student.total_marks,
+            student.num_subjects,
+            average,
+            export_percentage(student, max_marks, format).unwrap_or_default(),
+            csv_field(student.final_grade().as_str()), // The no-grade label is free text
+            csv_field(student.assign_grade().as_str())
+        )?;
+        if let Some((ranks, percentiles)) = &ranking {
+            let rank = ranks[index].map(|rank| rank.to_string()).unwrap_or_default();
+            let percentile = percentiles[index]
+                .map(|percentile| format!("{:.1}", percentile))
+                .unwrap_or_default();
+            write!(writer, ",{},{}", rank, percentile)?;
+        }
+        writeln!(writer)?;
+    }
+    Ok(())
+}
+
+/// Checks whether an existing export already starts with a header row.
+/// A leading rank comment row is skipped. An empty file has no header; a file
+/// whose header doesn't match `expected` is rejected rather than mixed with new rows.
+fn existing_csv_header(path: &str, expected: &str) -> io::Result<bool> {
+    let file = match File::open(path) {
+        Ok(file) => file,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(false), // Nothing to append to yet
+        Err(err) => return Err(err),
+    };
+
+    let mut lines = BufReader::new(file).lines();
+    let mut first = lines.next().transpose()?;
+    if first.as_deref().is_some_and(|line| line.starts_with('#')) {
+        first = lines.next().transpose()?; // Skip the ranking comment row
+    }
+    match first {
+        None => Ok(false),
+        Some(line) if line.trim_end() == expected => Ok(true),
+        Some(line) => Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("existing header '{}' does not match '{}'", line.trim_end(), expected),
+        )),
+    }
+}
+
+/// Writes the students to a CSV file at the given path. Normally the file is
+/// replaced; in append mode rows are added and the header is only written when
+/// the file doesn't already have one. Lines end with `line_ending`.
+fn export_csv(
+    path: &str,
+    students: &[&Student],
+    options: CsvExportOptions,
+    max_marks: &BTreeMap<String, u32>,
+    format: &ReportFormat,
+    line_ending: LineEnding,
+) -> io::Result<()> {
+    // Buffered, since every row is several small writes; flushed by hand so a
+    // failed final write is reported rather than lost when the writer is dropped
+    if !options.append {
+        let mut file = LineEndingWriter::new(BufWriter::new(File::create(path)?), line_ending);
+        write_csv(students, &mut file, options.with_rank, true, options.language, max_marks, format)?;
+        return file.flush();
+    }
+
+    let has_header = existing_csv_header(path, &csv_export_header(options.with_rank, format.grades_only, options.language))?;
+    let mut file = LineEndingWriter::new(BufWriter::new(OpenOptions::new().create(true).append(true).open(path)?), line_ending);
+    write_csv(students, &mut file, options.with_rank, !has_header, options.language, max_marks, format)?;
+    file.flush()
+}
+
+// ---
+// ### 10. XML Export
+// `--xml <file>` writes the class as XML for systems that ingest it. The element
+// names below are a stable contract; rename nothing without versioning:
+//
+// <students>
+//   <student>
+//     <student_id>…</student_id>         (empty when the source had no IDs)
+//     <name>…</name>
+//     <total_marks>…</total_marks>
+//     <num_subjects>…</num_subjects>
+//     <average>…</average>               (empty when there are no subjects)
+//     <grade>…</grade>                   (final grade, including any override)
+//     <computed_grade>…</computed_grade> (grade derived from the average)
+//   </student>
+// </students>
+// ---
+
+/// Escapes text for XML element content and attributes.
+fn xml_escape(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '&' => out.push_str("&amp;"),
+            '"' => out.push_str("&quot;"),
+            '\'' => out.push_str("&apos;"),
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+/// Renders the students as an XML document using the element names documented above.
+fn to_xml(students: &[&Student], format: &ReportFormat) -> String {
+    let mut xml = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<students>\n");
+    for student in students {
+        let average = student
+            .calculate_average()
+            .map(|average| format.average(average))
+            .unwrap_or_default();
+        xml.push_str("  <student>\n");
+        xml.push_str(&format!("    <student_id>{}</student_id>\n", xml_escape(&student.student_id)));
+        xml.push_str(&format!("    <name>{}</name>\n", xml_escape(&student.name)));
+        if format.grades_only {
+            xml.push_str(&format!("    <grade>{}</grade>\n", xml_escape(student.final_grade().as_str())));
+            xml.push_str("  </student>\n");
+            continue;
+        }
+        xml.push_str(&format!("    <total_marks>{}</total_marks>\n", student.total_marks));
+        xml.push_str(&format!("    <num_subjects>{}</num_subjects>\n", student.num_subjects));
+        xml.push_str(&format!("    <average>{}</average>\n", average));
+        xml.push_str(&format!("    <grade>{}</grade>\n", xml_escape(student.final_grade().as_str())));
+        xml.push_str(&format!(
+            "    <computed_grade>{}</computed_grade>\n",
+            xml_escape(student.assign_grade().as_str())
+        ));
+        xml.push_str("  </student>\n");
+    }
+    xml.push_str("</students>\n");
+    xml
+}
+
+/// Writes the students to an XML file at the given path.
+fn export_xml(path: &str, students: &[&Student], format: &ReportFormat, line_ending: LineEnding) -> io::Result<()> {
+    fs::write(path, line_ending.apply(&to_xml(students, format)))
+}
+
+// ---
+// ### 11. JSON, Markdown and HTML Export
+// Further export formats, chosen with `--output <file>` by file extension:
+// `.json` for other programs, `.md` for notes and wikis, `.html` for a browser.
+// ---
+
+/// The average as text for the exporters, or None when there are no subjects.
+fn export_average(student: &Student, format: &ReportFormat) -> Option<String> {
+    student.calculate_average().map(|average| format.average(average))
+}
+
+/// The student's percentage (see Student::percentage) as text for the exporters,
+/// with the same precision as averages, or None when there is no average.
+fn export_percentage(student: &Student, max_marks: &BTreeMap<String, u32>, format: &ReportFormat) -> Option<String> {
+    student.percentage(max_marks).map(|percentage| format.average(percentage))
+}
+
+/// Renders the students as a JSON array. Each object has `student_id`, `name`, `section`
+/// (null without one), `total_marks`, `num_subjects`, `average` (null without subjects), `percentage` (the marks out
+/// of the per-subject maximums in `max_marks`, as a number), `grade`, `computed_grade` and `subjects`
+/// (a list of `name`/`mark` objects, empty when only a total is known; an absence has
+/// the mark "AB"). A subject computed from weighted parts also has `components`, a
+/// list of `name`/`score`/`weight`. A student marked with internal and exam weights also
+/// has `internal`, `exam` and `weights` (an object with `internal` and `exam`), so
+/// reading the file back averages them the same way.
+fn to_json(students: &[&Student], max_marks: &BTreeMap<String, u32>, format: &ReportFormat) -> String {
+    let objects: Vec<String> = students
+        .iter()
+        .map(|student| {
+            if format.grades_only {
+                return format!(
+                    "  {{\"student_id\":{},\"name\":{},\"grade\":{}}}",
+                    json_string(&student.student_id),
+                    json_string(&student.name),
+                    json_string(student.final_grade().as_str())
+                );
+            }
+            let subjects: Vec<String> = student
+                .subjects
+                .iter()
+                .map(|subject| {
+                    let components: Vec<String> = subject
+                        .components
+                        .iter()
+                        .map(|part| {
+                            format!(
+                                "{{\"name\":{},\"score\":{},\"weight\":{}}}",
+                                json_string(&part.name),
+                                part.score,
+                                part.weight
+                            )
+                        })
+                        .collect();
+                    let components = if components.is_empty() {
+                        String::new()
+                    } else {
+                        format!(",\"components\":[{}]", components.join(","))
+                    };
+                    let mark = match subject.mark {
+                        Mark::Score(mark) => mark.to_string(),
+                        Mark::Absent => json_string("AB"),
+                    };
+                    format!("{{\"name\":{},\"mark\":{}{}}}", json_string(&subject.name), mark, components)
+                })
+                .collect();
+            let weights = match student.weights {
+                Some(weights) => format!(
+                    ",\"internal\":{},\"exam\":{},\"weights\":{{\"internal\":{},\"exam\":{}}}",
+                    student.internal, student.exam, weights.internal, weights.exam
+                ),
+                None => String::new(),
+            };
+            format!(
+                "  {{\"student_id\":{},\"name\":{},\"section\":{},\"total_marks\":{},\"num_subjects\":{}{},\"average\":{},\"percentage\":{},\"grade\":{},\"computed_grade\":{},\"subjects\":[{}]}}",
+                json_string(&student.student_id),
+                json_string(&student.name),
+                student.section.as_deref().map_or_else(|| "null".to_string(), json_string),
+                student.total_marks,
+                student.num_subjects,
+                weights,
+                export_average(student, format).unwrap_or_else(|| "null".to_string()),
+                export_percentage(student, max_marks, format).unwrap_or_else(|| "null".to_string()),
+                json_string(student.final_grade().as_str()),
+                json_string(student.assign_grade().as_str()),
+                subjects.join(",")
+            )
+        })
+        .collect();
+    format!("[\n{}\n]\n", objects.join(",\n"))
+}
+
+/// Renders the students as a Markdown table. Pipes in IDs and names are escaped so they
+/// can't split a cell. A `summary` follows the table under its own heading.
+fn to_markdown(students: &[&Student], format: &ReportFormat, summary: Option<&str>) -> String {
+    let escape = |text: &str| text.replace('|', "\\|");
+    let mut markdown = if format.grades_only {
+        String::from("| ID | Name | Grade |\n|---|---|:---:|\n")
+    } else {
+        String::from("| ID | Name | Total Marks | No. Subjects | Average | Grade |\n|---|---|---:|---:|---:|:---:|\n")
+    };
+    for student in students {
+        let grade = escape(student.final_grade().as_str());
+        if format.grades_only {
+            markdown.push_str(&format!("| {} | {} | {} |\n", escape(&student.student_id), escape(&student.name), grade));
+            continue;
+        }
+        markdown.push_str(&format!(
+            "| {} | {} | {} | {} | {} | {} |\n",
+            escape(&student.student_id),
+            escape(&student.name),
+            student.total_marks,
+            student.num_subjects,
+            export_average(student, format).unwrap_or_else(|| "N/A".to_string()),
+            grade
+        ));
+    }
+    if let Some(summary) = summary {
+        // Fenced so the column alignment survives rendering
+        markdown.push_str(&format!("\n## Class Summary\n\n```\n{}```\n", summary));
+    }
+    markdown
+}
+
+/// Renders the students as a standalone HTML page with one table row per student,
+/// followed by the `summary` as preformatted text when one is given.
+fn to_html(students: &[&Student], format: &ReportFormat, summary: Option<&str>) -> String {
+    let mut html = String::from(
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>Student Report Cards</title>\n</head>\n<body>\n",
+    );
+    if format.grades_only {
+        html.push_str("<table>\n<tr><th>ID</th><th>Name</th><th>Grade</th></tr>\n");
+    } else {
+        html.push_str("<table>\n<tr><th>ID</th><th>Name</th><th>Total Marks</th><th>No. Subjects</th><th>Average</th><th>Grade</th></tr>\n");
+    }
+    for student in students {
+        if format.grades_only {
+            html.push_str(&format!(
+                "<tr><td>{}</td><td>{}</td><td>{}</td></tr>\n",
+                xml_escape(&student.student_id),
+                xml_escape(&student.name),
+                xml_escape(student.final_grade().as_str())
+            ));
+            continue;
+        }
+        html.push_str(&format!(
+            "<tr><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td></tr>\n",
+            xml_escape(&student.student_id),
+            xml_escape(&student.name), // The XML escapes are also safe for HTML
+            student.total_marks,
+            student.num_subjects,
+            export_average(student, format).unwrap_or_else(|| "N/A".to_string()),
+            xml_escape(student.final_grade().as_str())
+        ));
+    }
+    html.push_str("</table>\n");
+    if let Some(summary) = summary {
+        html.push_str(&format!("<h2>Class Summary</h2>\n<pre>{}</pre>\n", xml_escape(summary)));
+    }
+    html.push_str("</body>\n</html>\n");
+    html
+}
+
+// ---
+// ### 12. JSON Import
+// `--input <file>.json` reads a file written by the JSON export back in and prints
+// the report cards again. Only the marks are taken from the file: averages and
+// grades are recomputed, so a re-rendered report reflects the current grading
+// scale. A `grade` that differs from the stored `computed_grade` was a manual
+// override and is kept as one.
+// ---
+
+/// Everything that can go wrong while reading a JSON export.
+#[derive(Debug)]
+enum JsonImportError {
+    Io(io::Error),                                  // The file could not be read
+    Syntax { line: usize, message: String },        // The text isn't valid JSON
+    InvalidStudent { index: usize, message: String }, // Valid JSON, but not a student record
+}
+
+impl std::fmt::Display for JsonImportError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            JsonImportError::Io(err) => write!(f, "could not read JSON: {}", err),
+            JsonImportError::Syntax { line, message } => write!(f, "line {}: invalid JSON: {}", line, message),
+            JsonImportError::InvalidStudent { index, message } => write!(f, "student {}: {}", index + 1, message),
+        }
+    }
+}
+
+impl From<io::Error> for JsonImportError {
+    fn from(err: io::Error) -> Self {
+        JsonImportError::Io(err)
+    }
+}
+
+/// A parsed JSON value. Objects keep their keys in file order.
+enum JsonValue {
+    Null,
+    Bool, // true or false; no field in a student record is a boolean, so the value isn't kept
+    Number(f64),
+    String(String),
+    Array(Vec<JsonValue>),
+    Object(Vec<(String, JsonValue)>),
+}
+
+impl JsonValue {
+    /// Looks up a key in an object; None for missing keys and non-objects.
+    fn get(&self, key: &str) -> Option<&JsonValue> {
+        match self {
+            JsonValue::Object(entries) => entries.iter().find(|(name, _)| name == key).map(|(_, value)| value),
+            _ => None,
+        }
+    }
+}
+
+/// A small recursive-descent JSON parser, enough for files this program writes
+/// (and hand edits of them). Tracks the line number for error messages.
+struct JsonParser {
+    chars: Vec<char>,
+    pos: usize,
+    line: usize,
+}
+
+impl JsonParser {
+    fn new(text: &str) -> Self {
+        Self { chars: text.chars().collect(), pos: 0, line: 1 }
+    }
+
+    /// Parses the whole text as one value; anything after it is an error.
+    fn parse_document(mut self) -> Result<JsonValue, JsonImportError> {
+        let value = self.parse_value()?;
+        self.skip_whitespace();
+        if self.pos < self.chars.len() {
+            return Err(self.error("unexpected text after the end of the document"));
+        }
+        Ok(value)
+    }
+
+    fn error(&self, message: &str) -> JsonImportError {
+        JsonImportError::Syntax { line: self.line, message: message.to_string() }
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.pos).copied()
+    }
+
+    fn next_char(&mut self) -> Option<char> {
+        let c = self.peek()?;
+        self.pos += 1;
+        if c == '\n' {
+            self.line += 1;
+        }
+        Some(c)
+    }
+
+    fn skip_whitespace(&mut self) {
+        while self.peek().is_some_and(|c| c.is_ascii_whitespace()) {
+            self.next_char();
+        }
+    }
+
+    fn expect(&mut self, expected: char) -> Result<(), JsonImportError> {
+        match self.next_char() {
+            Some(c) if c == expected => Ok(()),
+            _ => Err(self.error(&format!("expected '{}'", expected))),
+        }
+    }
+
+    /// Consumes a keyword such as `true`, returning the value it stands for.
+    fn keyword(&mut self, word: &str, value: JsonValue) -> Result<JsonValue, JsonImportError> {
+        for expected in word.chars() {
+            if self.next_char() != Some(expected) {
+                return Err(self.error(&format!("expected '{}'", word)));
+            }
+        }
+        Ok(value)
+    }
+
+    fn parse_value(&mut self) -> Result<JsonValue, JsonImportError> {
+        self.skip_whitespace();
+        match self.peek() {
+            Some('{') => self.parse_object(),
+            Some('[') => self.parse_array(),
+            Some('"') => Ok(JsonValue::String(self.parse_string()?)),
+            Some('t') => self.keyword("true", JsonValue::Bool),
+            Some('f') => self.keyword("false", JsonValue::Bool),
+            Some('n') => self.keyword("null", JsonValue::Null),
+            Some(c) if c == '-' || c.is_ascii_digit() => self.parse_number(),
+            Some(_) => Err(self.error("expected a value")),
+            None => Err(self.error("unexpected end of input")),
+        }
+    }
+
+    fn parse_object(&mut self) -> Result<JsonValue, JsonImportError> {
+        self.expect('{')?;
+        let mut entries = Vec::new();
+        self.skip_whitespace();
+        if self.peek() == Some('}') {
+            self.next_char();
+            return Ok(JsonValue::Object(entries));
+        }
+        loop {
+            self.skip_whitespace();
+            let key = self.parse_string()?;
+            self.skip_whitespace();
+            self.expect(':')?;
+            entries.push((key, self.parse_value()?));
+            self.skip_whitespace();
+            match self.next_char() {
+                Some(',') => continue,
+                Some('}') => return Ok(JsonValue::Object(entries)),
+                _ => return Err(self.error("expected ',' or '}' in object")),
+            }
+        }
+    }
+
+    fn parse_array(&mut self) -> Result<JsonValue, JsonImportError> {
+        self.expect('[')?;
+        let mut items = Vec::new();
+        self.skip_whitespace();
+        if self.peek() == Some(']') {
+            self.next_char();
+            return Ok(JsonValue::Array(items));
+        }
+        loop {
+            items.push(self.parse_value()?);
+            self.skip_whitespace();
+            match self.next_char() {
+                Some(',') => continue,
+                Some(']') => return Ok(JsonValue::Array(items)),
+                _ => return Err(self.error("expected ',' or ']' in array")),
+            }
+        }
+    }
+
+    fn parse_string(&mut self) -> Result<String, JsonImportError> {
+        self.expect('"')?;
+        let mut text = String::new();
+        loop {
+            match self.next_char() {
+                Some('"') => return Ok(text),
+                Some('\\') => {
+                    let escaped = match self.next_char() {
+                        Some('"') => '"',
+                        Some('\\') => '\\',
+                        Some('/') => '/',
+                        Some('b') => '\u{8}',
+                        Some('f') => '\u{c}',
+                        Some('n') => '\n',
+                        Some('r') => '\r',
+                        Some('t') => '\t',
+                        Some('u') => self.parse_unicode_escape()?,
+                        _ => return Err(self.error("invalid escape in string")),
+                    };
+                    text.push(escaped);
+                }
+                Some(c) => text.push(c),
+                None => return Err(self.error("unterminated string")),
+            }
+        }
+    }
+
+    /// Reads the four hex digits after `\u`, joining a surrogate pair if one follows.
+    fn parse_unicode_escape(&mut self) -> Result<char, JsonImportError> {
+        let first = self.parse_hex4()?;
+        let code = if (0xD800..0xDC00).contains(&first) {
+            if self.next_char() != Some('\\') || self.next_char() != Some('u') {
+                return Err(self.error("unpaired surrogate in string"));
+            }
+            let second = self.parse_hex4()?;
+            0x10000 + ((first - 0xD800) << 10) + (second.wrapping_sub(0xDC00) & 0x3FF)
+        } else {
+            first
+        };
+        char::from_u32(code).ok_or_else(|| self.error("invalid unicode escape"))
+    }
+
+    fn parse_hex4(&mut self) -> Result<u32, JsonImportError> {
+        let mut code = 0;
+        for _ in 0..4 {
+            let digit = self.next_char().and_then(|c| c.to_digit(16));
+            code = code * 16 + digit.ok_or_else(|| self.error("invalid unicode escape"))?;
+        }
+        Ok(code)
+    }
+
+    fn parse_number(&mut self) -> Result<JsonValue, JsonImportError> {
+        let start = self.pos;
+        while self.peek().is_some_and(|c| c.is_ascii_digit() || matches!(c, '-' | '+' | '.' | 'e' | 'E')) {
+            self.next_char();
+        }
+        let text: String = self.chars[start..self.pos].iter().collect();
+        text.parse::<f64>()
+            .map(JsonValue::Number)
+            .map_err(|_| self.error(&format!("invalid number '{}'", text)))
+    }
+}
+
+/// One student as stored in a JSON export. The stored average is ignored;
+/// only what's needed to rebuild the Student is kept.
+struct StudentDto {
+    student_id: String,
+    name: String,
+    section: Option<String>,
+    total_marks: u32,
+    num_subjects: u32,
+    weighted: Option<(u32, u32, Weights)>, // Internal marks, exam marks and their weights, when the file has them
+    grade: Option<Grade>,          // The final grade when the file was written
+    computed_grade: Option<Grade>, // The grade the average gave when the file was written
+    incomplete: bool,              // The grade was "I" when the file was written
+    subjects: Vec<Subject>,
+}
+
+impl StudentDto {
+    /// Reads the record at `index` in the export's array.
+    fn from_json(value: &JsonValue, index: usize) -> Result<Self, JsonImportError> {
+        let invalid = |message: String| JsonImportError::InvalidStudent { index, message };
+        let text = |value: &JsonValue, key: &str| match value.get(key) {
+            Some(JsonValue::String(text)) => Ok(text.clone()),
+            None | Some(JsonValue::Null) => Ok(String::new()),
+            Some(_) => Err(invalid(format!("'{}' must be a string", key))),
+        };
+        let count = |value: &JsonValue, key: &str| match value.get(key) {
+            Some(JsonValue::Number(n)) if n.fract() == 0.0 && (0.0..=u32::MAX as f64).contains(n) => Ok(*n as u32),
+            Some(_) => Err(invalid(format!("'{}' must be a whole number of at least 0", key))),
+            None => Err(invalid(format!("missing '{}'", key))),
+        };
+
+        if !matches!(value, JsonValue::Object(_)) {
+            return Err(invalid("expected an object".to_string()));
+        }
+        let number = |value: &JsonValue, key: &str| match value.get(key) {
+            Some(JsonValue::Number(n)) => Ok(*n),
+            Some(_) => Err(invalid(format!("'{}' must be a number", key))),
+            None => Err(invalid(format!("missing '{}'", key))),
+        };
+        let subject = |item: &JsonValue| match item.get("components") {
+            None | Some(JsonValue::Null) => {
+                let mark = match item.get("mark") {
+                    Some(JsonValue::String(text)) if Mark::is_absent_text(text) => Mark::Absent,
+                    _ => Mark::Score(count(item, "mark")?),
+                };
+                Ok(Subject { name: text(item, "name")?, mark, components: Vec::new() })
+            }
+            Some(JsonValue::Array(parts)) => {
+                let components = parts
+                    .iter()
+                    .map(|part| {
+                        let score = number(part, "score")?;
+                        if !(0.0..=MAX_SUBJECT_MARK as f64).contains(&score) {
+                            return Err(invalid(format!("'score' must be between 0 and {}", MAX_SUBJECT_MARK)));
+                        }
+                        Ok(Component { name: text(part, "name")?, score, weight: number(part, "weight")? })
+                    })
+                    .collect::<Result<_, JsonImportError>>()?;
+                Subject::with_components(text(item, "name")?, components).map_err(|err| invalid(err.to_string()))
+            }
+            Some(_) => Err(invalid("'components' must be a list".to_string())),
+        };
+        let subjects = match value.get("subjects") {
+            None | Some(JsonValue::Null) => Vec::new(),
+            Some(JsonValue::Array(items)) => items.iter().map(subject).collect::<Result<_, JsonImportError>>()?,
+            Some(_) => return Err(invalid("'subjects' must be a list".to_string())),
+        };
+        let weighted = match value.get("weights") {
+            None | Some(JsonValue::Null) => None,
+            Some(weights @ JsonValue::Object(_)) => {
+                let weights = Weights { internal: number(weights, "internal")?, exam: number(weights, "exam")? };
+                if (weights.internal + weights.exam - 1.0).abs() > 1e-9 {
+                    return Err(invalid("'weights' must add up to 1".to_string()));
+                }
+                Some((count(value, "internal")?, count(value, "exam")?, weights))
+            }
+            Some(_) => return Err(invalid("'weights' must be an object".to_string())),
+        };
+
+        Ok(Self {
+            student_id: text(value, "student_id")?,
+            name: text(value, "name")?,
+            section: Some(text(value, "section")?).filter(|section| !section.is_empty()),
+            total_marks: count(value, "total_marks")?,
+            num_subjects: count(value, "num_subjects")?,
+            weighted,
+            grade: Grade::from_letter(&text(value, "grade")?),
+            incomplete: text(value, "grade")?.trim().eq_ignore_ascii_case("I"),
+            computed_grade: Grade::from_letter(&text(value, "computed_grade")?),
+            subjects,
+        })
+    }
+
+    /// Rebuilds the Student with both the stored totals and the subject marks,
+    /// so the caller can check them against each other with verify_totals.
+    fn into_student(self) -> Student {
+        let mut student = match self.weighted {
+            Some((internal, exam, weights)) => {
+                Student::with_components(self.name, internal, exam, self.num_subjects, weights)
+            }
+            None => Student::new(self.name, self.total_marks, self.num_subjects),
+        };
+        student.subjects = self.subjects;
+        student.student_id = self.student_id;
+        student.section = self.section;
+        student.incomplete = self.incomplete;
+        if self.grade != self.computed_grade {
+            student.override_grade = self.grade; // Set by hand before the export
+        }
+        student
+    }
+}
+
+/// Reads the students from the text of a JSON export. When a record's stored
+/// subject count or totals disagree with its subject list, `strict` makes that
+/// an error; otherwise a warning is printed and the subject list wins. A subject listed
+/// twice for a student is merged (with a warning) or rejected as `duplicates` says.
+fn read_students_from_json(
+    text: &str,
+    strict: bool,
+    duplicates: DuplicateSubjects,
+) -> Result<Vec<Student>, JsonImportError> {
+    let JsonValue::Array(items) = JsonParser::new(text).parse_document()? else {
+        return Err(JsonImportError::Syntax { line: 1, message: "expected a list of students".to_string() });
+    };
+    items
+        .iter()
+        .enumerate()
+        .map(|(index, item)| {
+            let mut student = StudentDto::from_json(item, index)?.into_student();
+            // Checked on its own first, since a wrong count skews the average most
+            if let Err(mismatch) = student.verify_subject_count() {
+                let err = JsonImportError::InvalidStudent { index, message: mismatch.to_string() };
+                if strict {
+                    return Err(err);
+                }
+                eprintln!("Warning: {}; using the {} listed", err, mismatch.actual);
+                student.num_subjects = mismatch.actual;
+            }
+            if let Err(mismatch) = student.verify_totals() {
+                let err = JsonImportError::InvalidStudent { index, message: mismatch.to_string() };
+                if strict {
+                    return Err(err);
+                }
+                eprintln!("Warning: {}; using the subject marks", err);
+                student.recompute_totals();
+            }
+            let merged = student
+                .dedup_subjects(duplicates)
+                .map_err(|err| JsonImportError::InvalidStudent { index, message: err.to_string() })?;
+            if !merged.is_empty() {
+                eprintln!("Warning: student {}: merged duplicate subjects: {}", index + 1, merged.join(", "));
+            }
+            Ok(student)
+        })
+        .collect()
+}
+
+/// Opens and reads every student from the JSON export at the given path.
+fn load_json_file(path: &str, strict: bool, duplicates: DuplicateSubjects) -> Result<Vec<Student>, JsonImportError> {
+    read_students_from_json(&fs::read_to_string(path)?, strict, duplicates)
+}
+
+// ---
+// ### 13. Configuration File
+// Optional settings are read from a simple `key = value` text file passed with
+// `--config`. Blank lines and lines starting with '#' are ignored.
+// ---
+
+/// The per-subject pass mark used when `require_subject_pass` is on without a mark.
+const DEFAULT_SUBJECT_PASS_MARK: f64 = 40.0;
+
+/// The mark every core subject must reach when the config doesn't give `core_pass_mark`.
+const DEFAULT_CORE_PASS_MARK: f64 = 50.0;
+
+/// Subjects held to a higher bar than electives, e.g. Math and English.
+struct CoreRule {
+    subjects: Vec<String>, // Core subject names; every other subject is an elective
+    pass_mark: f64,        // The mark each core subject must reach to pass
+}
+
+/// Settings loaded from the configuration file.
+#[derive(Default)]
+struct Config {
+    weights: Option<Weights>, // Internal/exam weighting, when both weights are given
+    format: ReportFormat,     // Presentation settings such as decimal precision
+    subject_pass_mark: Option<f64>, // When set, every subject must reach this mark to pass outright
+    subject_pass_marks: BTreeMap<String, f64>, // Set by --pass-marks: subjects (lower-cased) with their own pass mark
+    core: Option<CoreRule>,          // Core subjects and the stricter mark each of them must reach
+    duplicate_subjects: DuplicateSubjects, // Set by --duplicate-subjects: how a subject entered twice is handled
+    line_ending: LineEnding,         // Set by --line-ending: how exported text files end their lines
+    previous: Option<ClassFile>,     // Set by --previous: last term's class, to compare grade bands with
+    curriculum: Option<Vec<String>>, // Set by --curriculum: the subjects every student should take
+    benchmark: Option<f64>,          // Set by --benchmark: a target average to compare the class with
+    grade_hint_window: Option<f64>,  // Report cards within this many marks of the next grade say so
+    explain: bool,                   // Set by --explain: report cards say why the student got their grade
+    input: InputPolicies,            // What an empty name, student ID or section means when entering students
+    no_summary: bool,                // Set by --no-summary: skip the class summary after the report cards
+    compare_scale: Option<GradingScale>, // Set by --compare-scale: a scale to re-grade the class with in the summary
+    commands: bool,                  // Set by --commands: type commands instead of picking from the menu
+    quick_add: Option<char>,         // Set by --quick-add: type whole students on one line, split at this delimiter
+    subject_names: Option<Vec<String>>, // Set by --subjects: every student's subjects, in entry order
+    no_grade_label: Option<String>,  // Shown instead of "N/A" for a student who can't be graded
+    credits: Option<BTreeMap<String, u32>>, // Credit hours by lower-cased subject name; turns on the class GPA
+    max_marks: BTreeMap<String, u32>, // Subjects (lower-cased) not marked out of MAX_SUBJECT_MARK, with their maximum
+    top: usize,                      // Set by --top: how many of the best students the summary lists
+    bottom: usize,                   // Set by --bottom: how many of the weakest students the summary lists
+}
+
+/// Everything that can go wrong while loading the configuration file.
+#[derive(Debug)]
+enum ConfigError {
+    Io(io::Error), // The file could not be read
+    InvalidLine { line: usize, content: String }, // A line is not of the form `key = value`
+    UnknownKey { line: usize, key: String },      // The key is not a recognised setting
+    InvalidValue { line: usize, key: String, value: String }, // The value could not be parsed
+    MissingWeight(&'static str),  // Only one of the two weights was given
+    WeightsDoNotSumToOne(f64),    // internal_weight + exam_weight != 1.0
+}
+
+impl std::fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            ConfigError::Io(err) => write!(f, "could not read config: {}", err),
+            ConfigError::InvalidLine { line, content } => {
+                write!(f, "config line {}: expected 'key = value', found '{}'", line, content)
+            }
+            ConfigError::UnknownKey { line, key } => write!(f, "config line {}: unknown key '{}'", line, key),
+            ConfigError::InvalidValue { line, key, value } => {
+                write!(f, "config line {}: invalid value '{}' for '{}'", line, value, key)
+            }
+            ConfigError::MissingWeight(key) => write!(f, "config: '{}' must also be set", key),
+            ConfigError::WeightsDoNotSumToOne(sum) => {
+                write!(f, "config: internal_weight and exam_weight must sum to 1.0 (got {})", sum)
+            }
+        }
+    }
+}
+
+impl From<io::Error> for ConfigError {
+    fn from(err: io::Error) -> Self {
+        ConfigError::Io(err)
+    }
+}
+
+/// Builds the error for a config value that is malformed or out of range.
+fn invalid_config_value(line: usize, key: &str, value: &str) -> ConfigError {
+    ConfigError::InvalidValue {
+        line,
+        key: key.to_string(),
+        value: value.to_string(),
+    }
+}
+
+/// Parses a floating-point config value, reporting the line and key on failure.
+fn parse_config_f64(value: &str, line: usize, key: &str) -> Result<f64, ConfigError> {
+    value.parse::<f64>().map_err(|_| invalid_config_value(line, key, value))
+}
+
+/// Reads configuration settings. Recognised keys:
+/// `internal_weight` and `exam_weight` (both required together, summing to 1.0),
+/// `precision` (decimal places for averages, 0 to 6), `max_name_length`
+/// (longest name shown on screen before it is shortened, at least 1), and
+/// `require_subject_pass` (true/false) with an optional `subject_pass_mark`
+/// (0 to 100, default 40): a passing average with any subject below that mark is
+/// reported as SUPPLEMENTARY. `grade_hint_window` (marks, at least 0) adds a
+/// line such as "2.50 marks from a B" to report cards that close to the next grade.
+/// `empty_name`, `empty_student_id` and `empty_section` set what an empty answer
+/// means (`reject`, `none`, or `default:<text>`; a name can't be `none`).
+/// `no_grade_label` replaces "N/A" as the grade of a student who can't be graded.
+/// `credits` gives subjects' credit hours, e.g. `Math:4, Art:2` (unlisted subjects
+/// count for 1), and adds the credit-weighted class GPA to the summary.
+/// `core_subjects` (e.g. `Math, English`) with an optional `core_pass_mark`
+/// (0 to 100, default 50) fails a passing student with a core subject below
+/// that mark, reported as FAIL (CORE SUBJECT); electives keep `subject_pass_mark`.
+/// `max_marks` gives what subjects are marked out of, e.g. `Lab:25` (unlisted
+/// subjects are out of 100), for the exported `percentage`.
+fn load_config(reader: impl BufRead) -> Result<Config, ConfigError> {
+    let mut internal_weight = None;
+    let mut exam_weight = None;
+    let mut format = ReportFormat::default();
+    let mut require_subject_pass = false;
+    let mut subject_pass_mark = DEFAULT_SUBJECT_PASS_MARK;
+    let mut grade_hint_window = None;
+    let mut input = InputPolicies::default();
+    let mut no_grade_label = None;
+    let mut credits = None;
+    let mut max_marks = BTreeMap::new();
+    let mut core_subjects: Option<Vec<String>> = None;
+    let mut core_pass_mark = DEFAULT_CORE_PASS_MARK;
+
+    for (index, line) in reader.lines().enumerate() {
+        let line_no = index + 1;
+        let line = line?;
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue; // Skip blank lines and comments
+        }
+
+        let (key, value) = line.split_once('=').ok_or_else(|| ConfigError::InvalidLine {
+            line: line_no,
+            content: line.to_string(),
+        })?;
+        let (key, value) = (key.trim(), value.trim());
+
+        match key {
+            "internal_weight" => internal_weight = Some(parse_config_f64(value, line_no, key)?),
+            "exam_weight" => exam_weight = Some(parse_config_f64(value, line_no, key)?),
+            "require_subject_pass" => {
+                require_subject_pass =
+                    value.parse::<bool>().map_err(|_| invalid_config_value(line_no, key, value))?;
+            }
+            "subject_pass_mark" => {
+                subject_pass_mark = parse_percent(value).ok_or_else(|| invalid_config_value(line_no, key, value))?;
+            }
+            "max_name_length" => {
+                format.max_name_length = value
+                    .parse::<usize>()
+                    .ok()
+                    .filter(|length| *length >= 1)
+                    .ok_or_else(|| invalid_config_value(line_no, key, value))?;
+            }
+            "empty_name" | "empty_student_id" | "empty_section" => {
+                let policy = EmptyInput::parse(value)
+                    .filter(|policy| !(key == "empty_name" && *policy == EmptyInput::None))
+                    .ok_or_else(|| invalid_config_value(line_no, key, value))?;
+                match key {
+                    "empty_name" => input.name = policy,
+                    "empty_student_id" => input.student_id = policy,
+                    _ => input.section = policy,
+                }
+            }
+            "no_grade_label" => {
+                // Must not be mistaken for a real grade when a file is read back in
+                let taken = value.is_empty() || Grade::from_letter(value).is_some() || value.eq_ignore_ascii_case("I");
+                if taken {
+                    return Err(invalid_config_value(line_no, key, value));
+                }
+                no_grade_label = Some(value.to_string());
+            }
+            "core_subjects" => {
+                let names: Vec<String> =
+                    value.split(',').map(|name| name.trim().to_string()).filter(|name| !name.is_empty()).collect();
+                if names.is_empty() {
+                    return Err(invalid_config_value(line_no, key, value));
+                }
+                core_subjects = Some(names);
+            }
+            "core_pass_mark" => {
+                core_pass_mark = parse_percent(value).ok_or_else(|| invalid_config_value(line_no, key, value))?;
+            }
+            "credits" => {
+                credits = Some(parse_credits(value).ok_or_else(|| invalid_config_value(line_no, key, value))?);
+            }
+            "max_marks" => {
+                max_marks = parse_max_marks(value).ok_or_else(|| invalid_config_value(line_no, key, value))?;
+            }
+            "grade_hint_window" => {
+                let window = parse_config_f64(value, line_no, key)?;
+                if !(window >= 0.0 && window.is_finite()) {
+                    return Err(invalid_config_value(line_no, key, value));
+                }
+                grade_hint_window = Some(window);
+            }
+            "precision" => {
+                format.precision =
+                    parse_precision(value).ok_or_else(|| invalid_config_value(line_no, key, value))?;
+            }
+            _ => {
+                return Err(ConfigError::UnknownKey {
+                    line: line_no,
+                    key: key.to_string(),
+                });
+            }
+        }
+    }
+
+    let weights = match (internal_weight, exam_weight) {
+        (None, None) => None,
+        (Some(_), None) => return Err(ConfigError::MissingWeight("exam_weight")),
+        (None, Some(_)) => return Err(ConfigError::MissingWeight("internal_weight")),
+        (Some(internal), Some(exam)) => {
+            let sum = internal + exam;
+            if (sum - 1.0).abs() > 1e-9 {
+                return Err(ConfigError::WeightsDoNotSumToOne(sum));
+            }
+            Some(Weights { internal, exam })
+        }
+    };
+
+    Ok(Config {
+        weights,
+        format,
+        subject_pass_mark: require_subject_pass.then_some(subject_pass_mark),
+        core: core_subjects.map(|subjects| CoreRule { subjects, pass_mark: core_pass_mark }),
+        duplicate_subjects: DuplicateSubjects::default(),
+        line_ending: LineEnding::default(),
+        previous: None,
+        curriculum: None,
+        benchmark: None,
+        subject_pass_marks: BTreeMap::new(),
+        grade_hint_window,
+        explain: false,
+        input,
+        no_summary: false,
+        commands: false,
+        quick_add: None,
+        compare_scale: None,
+        subject_names: None,
+        no_grade_label,
+        credits,
+        max_marks,
+        top: 0,
+        bottom: 0,
+    })
+}
+
+/// Parses a `credits` setting such as "Math:4, Art:2" into credit hours keyed
+/// by lower-cased subject name. Returns None if any entry is malformed.
+fn parse_credits(value: &str) -> Option<BTreeMap<String, u32>> {
+    value
+        .split(',')
+        .map(|entry| {
+            let (name, credits) = entry.split_once(':')?;
+            let name = name.trim();
+            if name.is_empty() {
+                return None;
+            }
+            Some((name.to_lowercase(), credits.trim().parse::<u32>().ok()?))
+        })
+        .collect()
+}
+
+/// Parses a `max_marks` setting such as "Lab:25, Project:50": what each subject
+/// is marked out of, keyed by lower-cased name. Each maximum must be from 1 to
+/// MAX_SUBJECT_MARK. Returns None if any entry is malformed.
+fn parse_max_marks(value: &str) -> Option<BTreeMap<String, u32>> {
+    parse_credits(value).filter(|max_marks| max_marks.values().all(|max| (1..=MAX_SUBJECT_MARK).contains(max)))
+}
+
+/// Parses a --pass-marks mapping such as "Math=40, Lab=25" into pass marks
+/// (from 0 to 100) keyed by lower-cased subject name. Blank entries are skipped;
+/// the error names the entry that is malformed or repeats a subject.
+fn parse_pass_marks(value: &str) -> Result<BTreeMap<String, f64>, String> {
+    let mut pass_marks = BTreeMap::new();
+    for entry in value.split(',').map(str::trim).filter(|entry| !entry.is_empty()) {
+        let (name, mark) = entry
+            .split_once('=')
+            .map(|(name, mark)| (name.trim(), mark))
+            .filter(|(name, _)| !name.is_empty())
+            .ok_or_else(|| format!("'{}' should be a subject and its pass mark, e.g. Math=40", entry))?;
+        let mark = parse_percent(mark).ok_or_else(|| format!("'{}': the pass mark must be from 0 to 100", entry))?;
+        if pass_marks.insert(name.to_lowercase(), mark).is_some() {
+            return Err(format!("'{}' is given more than once", name));
+        }
+    }
+    Ok(pass_marks)
+}
+
+/// Opens and reads the configuration file at the given path.
+fn load_config_file(path: &str) -> Result<Config, ConfigError> {
+    let file = File::open(path)?;
+    load_config(BufReader::new(file))
+}
+
+// ---
+// ### 14. Error Reporting
+// Errors are printed as human-readable text by default. With `--error-format json`
+// each error is written to stderr as a single JSON object, for example
+// {"error":"WrongColumnCount","line":12,"expected":3,"found":2,"message":"..."}.
+// ---
+
+/// How errors are written to stderr.
+#[derive(Clone, Copy, Default, PartialEq)]
+enum ErrorFormat {
+    #[default]
+    Human, // "Error: line 12: expected 3 columns but found 2"
+    Json,  // One JSON object per error
+}
+
+/// Structured details that every reportable error provides,
+/// so the JSON format never has to pick apart a pre-formatted message.
+trait ErrorDetails: std::fmt::Display {
+    /// A stable, machine-readable name for the kind of error (e.g. "WrongColumnCount").
+    fn kind(&self) -> &'static str;
+
+    /// The 1-based input line the error refers to, if any.
+    fn line(&self) -> Option<usize> {
+        None
+    }
+
+    /// Extra fields, as (name, already-encoded JSON value) pairs.
+    fn fields(&self) -> Vec<(&'static str, String)> {
+        Vec::new()
+    }
+}
+
+impl ErrorDetails for CsvError {
+    fn kind(&self) -> &'static str {
+        match self {
+            CsvError::Io(_) => "Io",
+            CsvError::WrongColumnCount { .. } => "WrongColumnCount",
+            CsvError::Invalid { error, .. } => error.kind(),
+            CsvError::InvalidScale { .. } => "InvalidScale",
+            CsvError::UnclosedQuote { .. } => "UnclosedQuote",
+        }
+    }
+
+    fn line(&self) -> Option<usize> {
+        match self {
+            CsvError::Io(_) => None,
+            CsvError::WrongColumnCount { line, .. }
+            | CsvError::Invalid { line, .. }
+            | CsvError::InvalidScale { line, .. }
+            | CsvError::UnclosedQuote { line } => Some(*line),
+        }
+    }
+
+    fn fields(&self) -> Vec<(&'static str, String)> {
+        match self {
+            CsvError::Io(_) => Vec::new(),
+            CsvError::WrongColumnCount { expected, found, .. } => {
+                vec![("expected", expected.to_string()), ("found", found.to_string())]
+            }
+            CsvError::Invalid { field, error, .. } => {
+                let mut fields = vec![("field", json_string(field))];
+                fields.extend(error.fields());
+                fields
+            }
+            CsvError::InvalidScale { value, .. } => vec![("value", json_string(value))],
+            CsvError::UnclosedQuote { .. } => Vec::new(),
+        }
+    }
+}
+
+impl ErrorDetails for ConfigError {
+    fn kind(&self) -> &'static str {
+        match self {
+            ConfigError::Io(_) => "Io",
+            ConfigError::InvalidLine { .. } => "InvalidLine",
+            ConfigError::UnknownKey { .. } => "UnknownKey",
+            ConfigError::InvalidValue { .. } => "InvalidValue",
+            ConfigError::MissingWeight(_) => "MissingWeight",
+            ConfigError::WeightsDoNotSumToOne(_) => "WeightsDoNotSumToOne",
+        }
+    }
+
+    fn line(&self) -> Option<usize> {
+        match self {
+            ConfigError::InvalidLine { line, .. }
+            | ConfigError::UnknownKey { line, .. }
+            | ConfigError::InvalidValue { line, .. } => Some(*line),
+            _ => None,
+        }
+    }
+
+    fn fields(&self) -> Vec<(&'static str, String)> {
+        match self {
+            ConfigError::UnknownKey { key, .. } => vec![("key", json_string(key))],
+            ConfigError::InvalidValue { key, value, .. } => {
+                vec![("key", json_string(key)), ("value", json_string(value))]
+            }
+            ConfigError::MissingWeight(key) => vec![("key", json_string(key))],
+            ConfigError::WeightsDoNotSumToOne(sum) => vec![("sum", sum.to_string())],
+            _ => Vec::new(),
+        }
+    }
+}
+
+impl ErrorDetails for JsonImportError {
+    fn kind(&self) -> &'static str {
+        match self {
+            JsonImportError::Io(_) => "Io",
+            JsonImportError::Syntax { .. } => "JsonSyntax",
+            JsonImportError::InvalidStudent { .. } => "InvalidStudent",
+        }
+    }
+
+    fn line(&self) -> Option<usize> {
+        match self {
+            JsonImportError::Syntax { line, .. } => Some(*line),
+            _ => None,
+        }
+    }
+
+    fn fields(&self) -> Vec<(&'static str, String)> {
+        match self {
+            JsonImportError::InvalidStudent { index, .. } => vec![("student", (index + 1).to_string())],
+            _ => Vec::new(),
+        }
+    }
+}
+
+/// An I/O failure on one of the program's files, labelled with what was being done.
+#[derive(Debug)]
+struct FileError {
+    what: &'static str, // e.g. "read roster" or "write PDF"
+    source: io::Error,
+}
+
+impl std::fmt::Display for FileError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "could not {}: {}", self.what, self.source)
+    }
+}
+
+impl ErrorDetails for FileError {
+    fn kind(&self) -> &'static str {
+        "Io"
+    }
+}
+
+/// Reported when --student names nobody in the class.
+#[derive(Debug)]
+struct StudentNotFound(String);
+
+impl std::fmt::Display for StudentNotFound {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "no student with ID or name '{}'", self.0)
+    }
+}
+
+impl ErrorDetails for StudentNotFound {
+    fn kind(&self) -> &'static str {
+        "StudentNotFound"
+    }
+
+    fn fields(&self) -> Vec<(&'static str, String)> {
+        vec![("student", json_string(&self.0))]
+    }
+}
+
+/// Encodes a string as a quoted JSON string, escaping quotes, backslashes and control characters.
+fn json_string(text: &str) -> String {
+    let mut out = String::with_capacity(text.len() + 2);
+    out.push('"');
+    for c in text.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if c.is_control() => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+/// Renders an error as a single-line JSON object.
+fn format_error_json(err: &impl ErrorDetails) -> String {
+    let mut parts = vec![format!("\"error\":{}", json_string(err.kind()))];
+    if let Some(line) = err.line() {
+        parts.push(format!("\"line\":{}", line));
+    }
+    for (name, value) in err.fields() {
+        parts.push(format!("{}:{}", json_string(name), value));
+    }
+    parts.push(format!("\"message\":{}", json_string(&err.to_string())));
+    format!("{{{}}}", parts.join(","))
+}
+
+/// Writes an error to stderr in the chosen format.
+fn report_error(format: ErrorFormat, err: &impl ErrorDetails) {
+    match format {
+        ErrorFormat::Human => eprintln!("Error: {}", err),
+        ErrorFormat::Json => eprintln!("{}", format_error_json(err)),
+    }
+}
+
+/// Stops the program after printing to stdout failed. A closed pipe means the
+/// reader (e.g. `head`) has seen enough, so that exits quietly.
+fn stdout_failed(format: ErrorFormat, what: &'static str, err: io::Error) -> ! {
+    if err.kind() == io::ErrorKind::BrokenPipe {
+        process::exit(0);
+    }
+    report_error(format, &FileError { what, source: err });
+    process::exit(1);
+}
+
+// ---
+// ### 15. PDF Export
+// With the optional `pdf` feature (`cargo build --features pdf`), `--pdf out.pdf`
+// writes one page per student showing the same fields as the console report card.
+// The writer produces a minimal PDF by hand, so the feature adds no dependencies.
+// ---
+
+/// Escapes text for use inside a PDF string literal. Characters outside ASCII
+/// are replaced with '?' because the built-in Courier font has no Unicode mapping.
+#[cfg(feature = "pdf")]
+fn pdf_escape(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '(' | ')' | '\\' => {
+                out.push('\\');
+                out.push(c);
+            }
+            c if c.is_ascii() && !c.is_ascii_control() => out.push(c),
+            _ => out.push('?'),
+        }
+    }
+    out
+}
+
+/// Builds the content stream that draws one student's report card on a page.
+#[cfg(feature = "pdf")]
+fn pdf_page_content(student: &Student, config: &Config) -> String {
+    let mut lines = vec![
+        "--- Student Report Card ---".to_string(),
+        format!("{:<15}: {}", "Name", student.name), // Exports keep the full name
+    ];
+    for (label, value) in student.report_fields(config) {
+        lines.push(format!("{:<15}: {}", label, value));
+    }
+    lines.push("---------------------------".to_string());
+
+    // Start near the top-left of an A4 page and move down one line per field
+    let mut content = String::from("BT\n/F1 12 Tf\n16 TL\n72 770 Td\n");
+    for line in lines {
+        content.push_str(&format!("({}) Tj T*\n", pdf_escape(&line)));
+    }
+    content.push_str("ET\n");
+    content
+}
+
+/// Writes a PDF document with one report-card page per student.
+#[cfg(feature = "pdf")]
+fn write_pdf(students: &[&Student], writer: &mut impl Write, config: &Config) -> io::Result<()> {
+    // Object layout: 1 = catalog, 2 = page tree, 3 = font, then a page and
+    // its content stream for every student (4 and 5, 6 and 7, ...).
+    let page_ids: Vec<usize> = (0..students.len()).map(|i| 4 + i * 2).collect();
+    let kids: Vec<String> = page_ids.iter().map(|id| format!("{} 0 R", id)).collect();
+
+    let mut objects = vec![
+        "<< /Type /Catalog /Pages 2 0 R >>".to_string(),
+        format!("<< /Type /Pages /Kids [{}] /Count {} >>", kids.join(" "), students.len()),
+        "<< /Type /Font /Subtype /Type1 /BaseFont /Courier >>".to_string(),
+    ];
+    for (student, page_id) in students.iter().zip(&page_ids) {
+        let content = pdf_page_content(student, config);
+        objects.push(format!(
+            "<< /Type /Page /Parent 2 0 R /MediaBox [0 0 595 842] /Resources << /Font << /F1 3 0 R >> >> /Contents {} 0 R >>",
+            page_id + 1
+        ));
+        objects.push(format!("<< /Length {} >>\nstream\n{}endstream", content.len(), content));
+    }
+
+    // Assemble the file, remembering each object's byte offset for the cross-reference table
+    let mut pdf = String::from("%PDF-1.4\n");
+    let mut offsets = Vec::with_capacity(objects.len());
+    for (index, object) in objects.iter().enumerate() {
+        offsets.push(pdf.len());
+        pdf.push_str(&format!("{} 0 obj\n{}\nendobj\n", index + 1, object));
+    }
+    let xref_offset = pdf.len();
+    pdf.push_str(&format!("xref\n0 {}\n0000000000 65535 f \n", objects.len() + 1));
+    for offset in offsets {
+        pdf.push_str(&format!("{:010} 00000 n \n", offset));
+    }
+    pdf.push_str(&format!(
+        "trailer\n<< /Size {} /Root 1 0 R >>\nstartxref\n{}\n%%EOF\n",
+        objects.len() + 1,
+        xref_offset
+    ));
+
+    writer.write_all(pdf.as_bytes())
+}
+
+/// Writes the report cards to a PDF file at the given path.
+#[cfg(feature = "pdf")]
+fn export_pdf(path: &str, students: &[&Student], config: &Config) -> io::Result<()> {
+    let mut file = File::create(path)?;
+    write_pdf(students, &mut file, config)
+}
+
+/// The error shown when `--pdf` is used in a build without the `pdf` feature.
+#[cfg(not(feature = "pdf"))]
+const PDF_DISABLED_MESSAGE: &str = "--pdf requires a build with PDF support (cargo build --features pdf)";
+
+// ---
+// ### 16. Export Formats
+// Every file format the program can write, so callers (and `--list-formats`)
+// can discover them and pick one from a file extension.
+// ---
+
+/// A supported export format. PDF is only available with the `pdf` feature.
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum ExportFormat {
+    Csv,
+    Json,
+    Markdown,
+    Html,
+    Xml,
+    #[cfg(feature = "pdf")]
+    Pdf,
+}
+
+/// Returned when a file extension doesn't match any supported export format.
+#[derive(Debug)]
+struct UnknownExtension(Option<String>);
+
+impl std::fmt::Display for UnknownExtension {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let supported: Vec<&str> = ExportFormat::all().iter().map(|format| format.extension()).collect();
+        match &self.0 {
+            Some(extension) => write!(f, "unknown file extension '.{}'", extension)?,
+            None => write!(f, "the file name has no extension")?,
+        }
+        write!(f, "; supported: {}", supported.join(", "))
+    }
+}
+
+impl ExportFormat {
+    /// Every format available in this build.
+    fn all() -> &'static [ExportFormat] {
+        &[
+            ExportFormat::Csv,
+            ExportFormat::Json,
+            ExportFormat::Markdown,
+            ExportFormat::Html,
+            ExportFormat::Xml,
+            #[cfg(feature = "pdf")]
+            ExportFormat::Pdf,
+        ]
+    }
+
+    /// A short human-readable name, e.g. "CSV".
+    fn name(&self) -> &'static str {
+        match self {
+            ExportFormat::Csv => "CSV",
+            ExportFormat::Json => "JSON",
+            ExportFormat::Markdown => "Markdown",
+            ExportFormat::Html => "HTML",
+            ExportFormat::Xml => "XML",
+            #[cfg(feature = "pdf")]
+            ExportFormat::Pdf => "PDF",
+        }
+    }
+
+    /// The file extension (without the dot) used for this format.
+    fn extension(&self) -> &'static str {
+        match self {
+            ExportFormat::Csv => "csv",
+            ExportFormat::Json => "json",
+            ExportFormat::Markdown => "md",
+            ExportFormat::Html => "html",
+            ExportFormat::Xml => "xml",
+            #[cfg(feature = "pdf")]
+            ExportFormat::Pdf => "pdf",
+        }
+    }
+
+    /// Picks the format for a file extension (with or without the dot, any case).
+    fn from_extension(extension: &str) -> Result<ExportFormat, UnknownExtension> {
+        let extension = extension.trim_start_matches('.');
+        ExportFormat::all()
+            .iter()
+            .copied()
+            .find(|format| format.extension().eq_ignore_ascii_case(extension))
+            .ok_or_else(|| UnknownExtension(Some(extension.to_string())))
+    }
+
+    /// What writing this format is called in error messages.
+    fn write_action(&self) -> &'static str {
+        match self {
+            ExportFormat::Csv => "write CSV export",
+            ExportFormat::Json => "write JSON export",
+            ExportFormat::Markdown => "write Markdown export",
+            ExportFormat::Html => "write HTML export",
+            ExportFormat::Xml => "write XML export",
+            #[cfg(feature = "pdf")]
+            ExportFormat::Pdf => "write PDF",
+        }
+    }
+}
+
+/// Picks the export format for an output path from its file extension.
+fn format_for_path(path: &str) -> Result<ExportFormat, UnknownExtension> {
+    match Path::new(path).extension().and_then(|extension| extension.to_str()) {
+        Some(extension) => ExportFormat::from_extension(extension),
+        None => Err(UnknownExtension(None)),
+    }
+}
+
+/// Copies the class with each student renamed "Student 001", "Student 002", ...
+/// in class order, for sharing outside the school. IDs are cleared too, since a
+/// roll number identifies a student as well as a name does. Marks are untouched.
+fn anonymize(students: &[Student]) -> Vec<Student> {
+    let width = students.len().to_string().len().max(3); // At least three digits, more for big classes
+    students
+        .iter()
+        .enumerate()
+        .map(|(index, student)| {
+            let mut copy = student.clone();
+            copy.name = format!("Student {:0width$}", index + 1, width = width);
+            copy.student_id = String::new();
+            copy
+        })
+        .collect()
+}
+
+/// Formats a count with comma thousands separators, e.g. 1200 as "1,200".
+fn format_thousands(count: usize) -> String {
+    let digits = count.to_string();
+    let mut formatted = String::new();
+    for (index, digit) in digits.chars().enumerate() {
+        if index > 0 && (digits.len() - index).is_multiple_of(3) {
+            formatted.push(',');
+        }
+        formatted.push(digit);
+    }
+    formatted
+}
+
+/// Describes an export before it is written, e.g.
+/// "About to write 1,200 records to report.csv (CSV)".
+fn export_notice(format: ExportFormat, path: &str, records: usize) -> String {
+    let noun = if records == 1 { "record" } else { "records" };
+    format!("About to write {} {} to {} ({})", format_thousands(records), noun, path, format.name())
+}
+
+/// Warns when an export's file extension names a different format than the one
+/// being written (e.g. `--xml report.csv`), since the file would be misread later.
+fn warn_on_extension_mismatch(format: ExportFormat, path: &str) {
+    let extension = Path::new(path).extension().and_then(|extension| extension.to_str());
+    if let Some(Ok(other)) = extension.map(ExportFormat::from_extension)
+        && other != format
+    {
+        eprintln!(
+            "Warning: '{}' has a .{} extension but is being written as {}",
+            path,
+            other.extension(),
+            format.name()
+        );
+    }
+}
+
+/// How lines end in exported text files. The exporters build text with '\n'
+/// and it is translated as the file is written, so every format agrees.
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum LineEnding {
+    Lf,   // "\n", as on Linux and macOS
+    CrLf, // "\r\n", as on Windows
+}
+
+impl Default for LineEnding {
+    /// The platform's own line ending.
+    fn default() -> Self {
+        if cfg!(windows) { LineEnding::CrLf } else { LineEnding::Lf }
+    }
+}
+
+impl LineEnding {
+    /// Parses a --line-ending value: `lf` or `crlf`.
+    fn parse(value: &str) -> Option<Self> {
+        match value.to_lowercase().as_str() {
+            "lf" => Some(LineEnding::Lf),
+            "crlf" => Some(LineEnding::CrLf),
+            _ => None,
+        }
+    }
+
+    /// The text with every '\n' ending written this way.
+    fn apply(self, text: &str) -> String {
+        match self {
+            LineEnding::Lf => text.to_string(),
+            LineEnding::CrLf => text.replace("\r\n", "\n").replace('\n', "\r\n"), // Existing "\r\n" kept as is
+        }
+    }
+}
+
+/// Wraps a writer so the '\n' in everything written to it becomes the chosen
+/// line ending, for exporters that write as they go rather than build a string.
+/// A '\n' that already follows a '\r' is left alone.
+struct LineEndingWriter<W: Write> {
+    inner: W,
+    line_ending: LineEnding,
+    after_cr: bool, // The last byte written was '\r', so a '\n' starting the next write is already a CRLF
+}
+
+impl<W: Write> LineEndingWriter<W> {
+    fn new(inner: W, line_ending: LineEnding) -> Self {
+        Self { inner, line_ending, after_cr: false }
+    }
+}
+
+impl<W: Write> Write for LineEndingWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if self.line_ending == LineEnding::Lf {
+            return self.inner.write(buf);
+        }
+        // Written in full so a '\n' is never split from the '\r' put before it
+        let mut start = 0;
+        for (index, byte) in buf.iter().enumerate() {
+            let after_cr = if index == 0 { self.after_cr } else { buf[index - 1] == b'\r' };
+            if *byte == b'\n' && !after_cr {
+                self.inner.write_all(&buf[start..index])?;
+                self.inner.write_all(b"\r")?;
+                start = index; // The '\n' goes out with the rest
+            }
+        }
+        self.inner.write_all(&buf[start..])?;
+        if let Some(last) = buf.last() {
+            self.after_cr = *last == b'\r';
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// Writes the students in the given format to the file at `path`. A `summary`
+/// (from --with-summary) is appended by the Markdown and HTML exports. Text
+/// formats end their lines as `config.line_ending` says.
+fn write_export(
+    format: ExportFormat,
+    path: &str,
+    students: &[&Student],
+    csv_options: CsvExportOptions,
+    summary: Option<&str>,
+    config: &Config,
+) -> io::Result<()> {
+    let ending = config.line_ending;
+    match format {
+        ExportFormat::Csv => {
+            export_csv(path, students, csv_options, &config.max_marks, &config.format, ending)
+        }
+        ExportFormat::Json => fs::write(path, ending.apply(&to_json(students, &config.max_marks, &config.format))),
+        ExportFormat::Markdown => fs::write(path, ending.apply(&to_markdown(students, &config.format, summary))),
+        ExportFormat::Html => fs::write(path, ending.apply(&to_html(students, &config.format, summary))),
+        ExportFormat::Xml => export_xml(path, students, &config.format, ending),
+        #[cfg(feature = "pdf")]
+        ExportFormat::Pdf => export_pdf(path, students, config),
+    }
+}
+
+// ---
+// ### 17. Generated Test Data
+// A fabricated class for tests and benchmarks, only built with the `testutil`
+// feature (or under `cargo test`) so it never ships in a normal binary.
+// ---
+
+/// Generates `count` students with varied names and per-subject marks.
+/// The same seed always produces the same class, so fixtures stay stable.
+/// Each student gets an overall ability and marks scattered around it, which
+/// spreads the class across all the grade bands.
+#[cfg(any(test, feature = "testutil"))]
+fn generate_students(count: usize, seed: u64) -> Vec<Student> {
+    const FIRST_NAMES: [&str; 10] = ["Asha", "Ben", "Chen", "Divya", "Elena", "Farid", "Grace", "Hiro", "Isla", "Jamal"];
+    const LAST_NAMES: [&str; 8] = ["Rao", "Smith", "Okafor", "Nguyen", "Garcia", "Khan", "Muller", "Sato"];
+    const SUBJECTS: [&str; 6] = ["Maths", "Science", "English", "History", "Art", "Computing"];
+
+    // SplitMix64: a tiny, well-mixed generator that needs no external crate
+    let mut state = seed;
+    let mut next = move || {
+        state = state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    };
+
+    (0..count)
+        .map(|index| {
+            let first = FIRST_NAMES[(next() % FIRST_NAMES.len() as u64) as usize];
+            let last = LAST_NAMES[(next() % LAST_NAMES.len() as u64) as usize];
+            let ability = 30 + next() % 66; // 30..=95, the student's typical mark
+            let num_subjects = 3 + (next() % 4) as usize; // 3..=6 subjects
+            let subjects = SUBJECTS[..num_subjects]
+                .iter()
+                .map(|name| {
+                    let spread = (next() % 31) as i64 - 15; // Up to 15 marks either side
+                    let mark = (ability as i64 + spread).clamp(0, MAX_SUBJECT_MARK as i64) as u32;
+                    Subject { name: name.to_string(), mark: Mark::Score(mark), components: Vec::new() }
+                })
+                .collect();
+            // The index keeps names unique even when first and last names repeat
+            let mut student = Student::with_subjects(format!("{} {} {}", first, last, index + 1), subjects);
+            student.student_id = format!("S{:04}", index + 1);
+            student
+        })
+        .collect()
+}
+
+/// Shown when `--generate` is used in a build without the `testutil` feature.
+#[cfg(not(feature = "testutil"))]
+const TESTUTIL_DISABLED_MESSAGE: &str =
+    "--generate is not available in this build; rebuild with `cargo build --features testutil`";
+
+/// ---
+/// ### 18. Command-Line Options
+/// The program runs interactively by default. Flags switch it to other modes.
+/// ---
+#[derive(Default)]
+struct Options {
+    csv_path: Option<String>,    // --csv <file>: load students from a CSV file instead of prompting
+    no_summary: bool,            // --no-summary: print the report cards without the class summary
+    compare_scale: Option<GradingScale>, // --compare-scale A,B,C: count how many grades a new scale would move
+    legend: bool,                // --legend: explain the grades and markers before the reports
+    explain: bool,               // --explain: say on each report card why the student got their grade
+    custom_scale: bool,          // --custom-scale: type in the grade cutoffs before entering students
+    non_interactive: bool,       // --non-interactive: fail instead of prompting, for scripts and CI
+    commands: bool,              // --commands: a command prompt (add, list, show, ...) instead of the menu
+    quick_add: bool,             // --quick-add: start by typing whole students, one per line
+    quick_delimiter: Option<char>, // --quick-delimiter <char>: what separates the fields for --quick-add
+    json_path: Option<String>,   // --input <file>.json: re-read a JSON export instead of prompting
+    merge_path: Option<String>,  // --merge <file>: re-sit records (.csv or .json) to fold into the class by ID
+    transcript_paths: Vec<String>, // --transcript <file>, repeated: term files, oldest first, for transcripts
+    previous_path: Option<String>, // --previous <file>: last term's class (.csv or .json) for the summary to compare with
+    continue_on_error: bool,     // --continue-on-error: skip bad CSV rows instead of stopping
+    strict: bool,                // --strict: reject JSON records whose count or totals disagree with their subjects
+    duplicate_subjects: DuplicateSubjects, // --duplicate-subjects latest|highest|reject
+    line_ending: LineEnding,     // --line-ending lf|crlf: line endings in exported text files
+    report_style: ReportStyle,   // --format card|compact: how batch mode prints each student
+    summary_only: bool,          // --summary-only: print the class summary without the report cards
+    announce: bool,              // --announce: print the report cards from the lowest rank up to rank 1
+    student: Option<String>,     // --student <id>: print only this student's report card (by ID or name)
+    ignore_zeros: bool,          // --ignore-zeros: leave subjects marked 0 out of averages
+    benchmark: Option<f64>,      // --benchmark <0-100>: compare the class mean with this average
+    pass_marks: BTreeMap<String, f64>, // --pass-marks "Math=40,Lab=25": pass marks for particular subjects
+    top: usize,                  // --top <n>: list the n best students in the summary
+    bottom: usize,               // --bottom <n>: list the n weakest students in the summary
+    anonymize: bool,             // --anonymize: replace names in exports (the console keeps them)
+    config_path: Option<String>, // --config <file>: read settings such as assessment weights
+    roster_path: Option<String>, // --roster <file>: prompt for marks for each listed name in turn
+    curriculum_path: Option<String>, // --curriculum <file>: the expected subjects, one per line
+    error_format: ErrorFormat,   // --error-format human|json: how errors are written to stderr
+    entry_mode: EntryMode,       // --per-subject / --per-mark / --letter-grades: how marks are entered
+    subject_names: Option<Vec<String>>, // --subjects "Math,Science": fixed subject names, asked for in order
+    exports: Vec<(ExportFormat, String)>, // --output / --export-csv / --xml / --pdf <file>: files to write
+    csv_export: CsvExportOptions, // --with-rank / --append: how the CSV export is written
+    with_summary: bool,          // --with-summary: end Markdown and HTML exports with the class summary
+    list_formats: bool,          // --list-formats: print the supported export formats and exit
+    force_color: bool,           // --force-color: keep colors even when stdout isn't a terminal
+    clear: bool,                 // --clear: clear the terminal before each student is entered
+    grades_only: bool,           // --grades-only: letter grades without marks or averages, on screen and in exports
+    trim_zeros: bool,            // --trim-zeros: drop trailing zeros from averages, on screen and in exports
+    precision: Option<usize>,    // --precision <0-6>: decimal places for averages (overrides the config)
+    grade_filter: Vec<Grade>,    // --filter-grade C,D: only print and export these grades (empty = all)
+    min_grade: Option<Grade>,    // --min-grade C: raise any lower grade to this one
+    max_grade: Option<Grade>,    // --max-grade B: lower any higher grade to this one
+    #[cfg(feature = "testutil")]
+    generate: Option<usize>,     // --generate <count>: fabricate a class instead of reading one
+    #[cfg(feature = "testutil")]
+    seed: u64,                   // --seed <n>: which generated class to produce
+}
+
+/// Parses the command-line arguments (excluding the program name) into Options.
+/// Returns an error message for unknown flags or missing values.
+fn parse_args(args: impl IntoIterator<Item = String>) -> Result<Options, String> {
+    let mut options = Options::default();
+    let mut args = args.into_iter();
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--csv" => {
+                options.csv_path = Some(args.next().ok_or("--csv requires a file path")?);
+            }
+            "--input" => {
+                // The extension decides how the file is read
+                let path = args.next().ok_or("--input requires a file path")?;
+                match format_for_path(&path) {
+                    Ok(ExportFormat::Csv) => options.csv_path = Some(path),
+                    Ok(ExportFormat::Json) => options.json_path = Some(path),
+                    _ => return Err(format!("--input '{}': only .csv and .json files can be read", path)),
+                }
+            }
+            "--merge" => {
+                let path = args.next().ok_or("--merge requires a file path")?;
+                if !matches!(format_for_path(&path), Ok(ExportFormat::Csv | ExportFormat::Json)) {
+                    return Err(format!("--merge '{}': only .csv and .json files can be read", path));
+                }
+                options.merge_path = Some(path);
+            }
+            "--previous" => {
+                let path = args.next().ok_or("--previous requires a file path")?;
+                if !matches!(format_for_path(&path), Ok(ExportFormat::Csv | ExportFormat::Json)) {
+                    return Err(format!("--previous '{}': only .csv and .json files can be read", path));
+                }
+                options.previous_path = Some(path);
+            }
+            "--transcript" => {
+                let path = args.next().ok_or("--transcript requires a file path")?;
+                if !matches!(format_for_path(&path), Ok(ExportFormat::Csv | ExportFormat::Json)) {
+                    return Err(format!("--transcript '{}': only .csv and .json files can be read", path));
+                }
+                options.transcript_paths.push(path);
+            }
+            "--summary-only" => options.summary_only = true,
+            "--announce" => options.announce = true,
+            "--student" => {
+                options.student = Some(args.next().ok_or("--student requires a student ID or name")?);
+            }
+            "--no-summary" => options.no_summary = true,
+            "--continue-on-error" => options.continue_on_error = true,
+            "--strict" => options.strict = true,
+            "--line-ending" => {
+                let value = args.next().ok_or("--line-ending requires lf or crlf")?;
+                options.line_ending =
+                    LineEnding::parse(&value).ok_or_else(|| format!("invalid --line-ending '{}': use lf or crlf", value))?;
+            }
+            "--duplicate-subjects" => {
+                let value = args.next().ok_or("--duplicate-subjects requires latest, highest or reject")?;
+                options.duplicate_subjects = DuplicateSubjects::parse(&value)
+                    .ok_or_else(|| format!("invalid --duplicate-subjects '{}': use latest, highest or reject", value))?;
+            }
+            "--ignore-zeros" => options.ignore_zeros = true,
+            "--anonymize" => options.anonymize = true,
+            "--benchmark" => {
+                let value = args.next().ok_or("--benchmark requires an average from 0 to 100")?;
+                let benchmark = parse_percent(&value).ok_or_else(|| format!("invalid --benchmark '{}': use an average from 0 to 100", value))?;
+                options.benchmark = Some(benchmark);
+            }
+            "--pass-marks" => {
+                let value = args.next().ok_or("--pass-marks requires a list such as \"Math=40,Lab=25\"")?;
+                options.pass_marks = parse_pass_marks(&value).map_err(|err| format!("invalid --pass-marks: {}", err))?;
+            }
+            "--top" | "--bottom" => {
+                let value = args.next().ok_or_else(|| format!("{} requires a number of students", arg))?;
+                let n = value.parse::<usize>().map_err(|_| format!("invalid {} '{}'", arg, value))?;
+                if arg == "--top" { options.top = n } else { options.bottom = n }
+            }
+            "--format" => {
+                options.report_style = match args.next().as_deref() {
+                    Some("card") => ReportStyle::Card,
+                    Some("compact") => ReportStyle::Compact,
+                    _ => return Err("--format must be 'card' or 'compact'".to_string()),
+                };
+            }
+            "--config" => {
+                options.config_path = Some(args.next().ok_or("--config requires a file path")?);
+            }
+            "--roster" => {
+                options.roster_path = Some(args.next().ok_or("--roster requires a file path")?);
+            }
+            "--curriculum" => {
+                options.curriculum_path = Some(args.next().ok_or("--curriculum requires a file path")?);
+            }
+            "--lang" => {
+                let code = args.next().ok_or("--lang requires a language code")?;
+                options.csv_export.language =
+                    Language::parse(&code).ok_or_else(|| format!("unsupported language '{}' (expected en or es)", code))?;
+            }
+            "--error-format" => {
+                options.error_format = match args.next().as_deref() {
+                    Some("human") => ErrorFormat::Human,
+                    Some("json") => ErrorFormat::Json,
+                    _ => return Err("--error-format must be 'human' or 'json'".to_string()),
+                };
+            }
+            "--pdf" => {
+                #[cfg(not(feature = "pdf"))]
+                return Err(PDF_DISABLED_MESSAGE.to_string());
+                #[cfg(feature = "pdf")]
+                {
+                    let path = args.next().ok_or("--pdf requires a file path")?;
+                    options.exports.push((ExportFormat::Pdf, path));
+                }
+            }
+            "--subjects" => {
+                let value = args.next().ok_or("--subjects requires a list such as 'Math,Science,English'")?;
+                options.subject_names = Some(parse_subject_list(&value)?);
+            }
+            "--per-subject" => options.entry_mode = EntryMode::PerSubject,
+            "--per-mark" => options.entry_mode = EntryMode::PerMark,
+            "--letter-grades" => options.entry_mode = EntryMode::Letters,
+            "--export-csv" => {
+                let path = args.next().ok_or("--export-csv requires a file path")?;
+                options.exports.push((ExportFormat::Csv, path));
+            }
+            "--xml" => {
+                let path = args.next().ok_or("--xml requires a file path")?;
+                options.exports.push((ExportFormat::Xml, path));
+            }
+            "--output" => {
+                let path = args.next().ok_or("--output requires a file path")?;
+                let format = format_for_path(&path).map_err(|err| format!("--output '{}': {}", path, err))?;
+                options.exports.push((format, path));
+            }
+            "--filter-grade" => {
+                let value = args.next().ok_or("--filter-grade requires grades such as 'D' or 'C,D'")?;
+                options.grade_filter = parse_grade_list(&value)
+                    .ok_or_else(|| format!("invalid --filter-grade '{}': use letters A-D, e.g. 'C,D'", value))?;
+            }
+            "--min-grade" | "--max-grade" => {
+                let value = args.next().ok_or_else(|| format!("{} requires a grade from A to D", arg))?;
+                let grade = Grade::from_letter(&value).ok_or_else(|| format!("invalid {} '{}': use A-D", arg, value))?;
+                if arg == "--min-grade" {
+                    options.min_grade = Some(grade);
+                } else {
+                    options.max_grade = Some(grade);
+                }
+            }
+            "--list-formats" => options.list_formats = true,
+            "--force-color" => options.force_color = true,
+            "--clear" => options.clear = true,
+            "--commands" => options.commands = true,
+            "--quick-add" => options.quick_add = true,
+            "--quick-delimiter" => {
+                let value = args.next().ok_or("--quick-delimiter requires a character")?;
+                let mut chars = value.chars();
+                options.quick_delimiter = match (chars.next(), chars.next()) {
+                    (Some(delimiter), None) => Some(delimiter),
+                    _ => return Err(format!("--quick-delimiter must be a single character, got '{}'", value)),
+                };
+            }
+            "--non-interactive" => options.non_interactive = true,
+            "--custom-scale" => options.custom_scale = true,
+            "--legend" => options.legend = true,
+            "--explain" => options.explain = true,
+            "--compare-scale" => {
+                let value = args.next().ok_or("--compare-scale requires cutoffs such as 85,70,55")?;
+                let scale = parse_scale(&value).map_err(|err| format!("invalid --compare-scale '{}': {}", value, err))?;
+                options.compare_scale = Some(scale);
+            }
+            "--with-rank" => options.csv_export.with_rank = true,
+            "--append" => options.csv_export.append = true,
+            "--with-summary" => options.with_summary = true,
+            "--grades-only" => options.grades_only = true,
+            "--trim-zeros" => options.trim_zeros = true,
+            "--seed" => {
+                // Picks the class made by --generate; other builds accept and ignore it
+                let value = args.next().ok_or("--seed requires a number")?;
+                let _seed = value.parse::<u64>().map_err(|_| format!("invalid --seed '{}'", value))?;
+                #[cfg(feature = "testutil")]
+                {
+                    options.seed = _seed;
+                }
+            }
+            "--generate" => {
+                #[cfg(not(feature = "testutil"))]
+                return Err(TESTUTIL_DISABLED_MESSAGE.to_string());
+                #[cfg(feature = "testutil")]
+                {
+                    let value = args.next().ok_or("--generate requires a number of students")?;
+                    options.generate =
+                        Some(value.parse().map_err(|_| format!("invalid --generate count '{}'", value))?);
+                }
+            }
+            "--precision" => {
+                let value = args.next().ok_or("--precision requires a number from 0 to 6")?;
+                options.precision =
+                    Some(parse_precision(&value).ok_or("--precision must be a number from 0 to 6")?);
+            }
+            other => return Err(format!("unknown argument '{}'", other)),
+        }
+    }
+
+    if options.subject_names.is_some() {
+        // Fixed names only make sense when marks are entered subject by subject
+        if matches!(options.entry_mode, EntryMode::PerMark | EntryMode::Letters) {
+            return Err("--subjects can't be combined with --per-mark or --letter-grades".to_string());
+        }
+        options.entry_mode = EntryMode::PerSubject;
+    }
+    if options.grades_only && options.csv_export.with_rank {
+        return Err("--with-rank can't be used with --grades-only".to_string());
+    }
+    if options.student.is_some() && (options.summary_only || options.announce) {
+        return Err("--student can't be used with --summary-only or --announce".to_string());
+    }
+    if options.summary_only && options.announce {
+        return Err("--summary-only and --announce can't be used together".to_string());
+    }
+    if options.summary_only && options.no_summary {
+        return Err("--summary-only and --no-summary can't be used together".to_string());
+    }
+    if !options.transcript_paths.is_empty() && (options.csv_path.is_some() || options.json_path.is_some()) {
+        return Err("--transcript reads its own term files and can't be used with --csv or --input".to_string());
+    }
+    if options.merge_path.is_some() && options.csv_path.is_none() && options.json_path.is_none() {
+        return Err("--merge requires --csv or --input".to_string());
+    }
+    if options.commands && (options.csv_path.is_some() || options.json_path.is_some()) {
+        return Err("--commands only applies when entering students interactively".to_string());
+    }
+    if let (Some(min), Some(max)) = (options.min_grade, options.max_grade)
+        && min > max
+    {
+        return Err(format!("--min-grade {} is above --max-grade {}", min.as_str(), max.as_str()));
+    }
+    let has_names = options.csv_path.is_some() || options.json_path.is_some() || options.roster_path.is_some();
+    if options.quick_add && has_names {
+        return Err("--quick-add only applies when entering students interactively without a roster".to_string());
+    }
+    if options.quick_delimiter.is_some() && !options.quick_add {
+        return Err("--quick-delimiter requires --quick-add".to_string());
+    }
+    if options.custom_scale && (options.csv_path.is_some() || options.json_path.is_some()) {
+        return Err("--custom-scale only applies when entering students interactively".to_string());
+    }
+    if options.continue_on_error && options.csv_path.is_none() {
+        return Err("--continue-on-error requires a CSV input".to_string());
+    }
+    if !options.exports.iter().any(|(format, _)| *format == ExportFormat::Csv) {
+        if options.csv_export.with_rank {
+            return Err("--with-rank requires a CSV export".to_string());
+        }
+        if options.csv_export.append {
+            return Err("--append requires a CSV export".to_string());
+        }
+    }
+
+    let summary_export = |format: &ExportFormat| matches!(format, ExportFormat::Markdown | ExportFormat::Html);
+    if options.with_summary && !options.exports.iter().any(|(format, _)| summary_export(format)) {
+        return Err("--with-summary requires a Markdown or HTML export".to_string());
+    }
+
+    Ok(options)
+}
+
+/// Splits a --subjects value such as "Math, Science,English" into names.
+/// Blank entries are dropped; the list must name at least one subject, no more
+/// than MAX_SUBJECTS, and no subject twice (ignoring case).
+fn parse_subject_list(value: &str) -> Result<Vec<String>, String> {
+    let names: Vec<String> =
+        value.split(',').map(str::trim).filter(|name| !name.is_empty()).map(str::to_string).collect();
+    if names.is_empty() || names.len() > MAX_SUBJECTS as usize {
+        return Err(format!("--subjects must list between 1 and {} subjects", MAX_SUBJECTS));
+    }
+    for (index, name) in names.iter().enumerate() {
+        if names[..index].iter().any(|earlier| earlier.eq_ignore_ascii_case(name)) {
+            return Err(format!("--subjects lists '{}' more than once", name));
+        }
+    }
+    Ok(names)
+}
+
+/// How batch mode prints each student.
+#[derive(Clone, Copy, Default)]
+enum ReportStyle {
+    #[default]
+    Card,    // The full multi-line report card
+    Compact, // One line per student (see Student::format_compact)
+}
+
+/// The usage text shown after a command-line error.
+const USAGE: &str = "\
+Usage: app [options]
+  --csv <file>             Load students from a CSV file instead of prompting
+  --input <file>           Load students from a .csv file or a JSON export (.json)
+  --merge <file>           Merge re-sit records (.csv or .json) into the class by student ID
+  --previous <file>        Compare the grade distribution with last term's class (.csv or .json)
+  --transcript <file>      Print each student's transcript across terms; repeat once per term, oldest first
+  --continue-on-error      Skip invalid CSV rows and list them at the end
+  --strict                 Reject JSON records whose subject count or totals don't match their subjects
+  --duplicate-subjects latest|highest|reject
+                           Merge a subject given twice keeping the latest or highest mark, or reject it
+  --format card|compact    Print full report cards, or one line per student
+  --summary-only           Print only the class summary when reading a file, without the report cards
+  --announce               Print the report cards by rank, from the lowest up to rank 1
+  --student <id>           Print only this student's report card (by ID or name), without the summary
+  --no-summary             Print the report cards without the class summary
+  --ignore-zeros           Treat subjects marked 0 as not taken when averaging
+  --benchmark <0-100>      Compare the class mean with a target average in the summary
+  --pass-marks <list>      Pass marks for particular subjects, e.g. \"Math=40,Lab=25\"; every subject
+                           must then reach its own mark (others the default) to pass outright
+  --top <n>                List the n students with the highest averages in the summary
+  --bottom <n>             List the n students with the lowest averages in the summary
+  --roster <file>          Prompt for marks for each name listed in the file
+  --per-subject            Enter each subject's name and mark
+  --subjects <list>        Enter marks for these subjects in order, e.g. \"Math,Science,English\"
+  --per-mark               Enter one mark per subject and let the program add them up
+  --letter-grades          Enter a letter grade per subject
+  --custom-scale           Type in the A, B and C cutoffs before entering students
+  --compare-scale <a,b,c>  Show in the summary how many grades would move on this scale
+  --config <file>          Read settings such as weights and precision
+  --curriculum <file>      Check each student took exactly the subjects listed
+  --precision <0-6>        Decimal places for averages
+  --grades-only            Show only names and letter grades, on screen and in exports
+  --trim-zeros             Print averages such as 80.00 as 80 and 78.50 as 78.5
+  --legend                 Explain the grade bands and pass rules before the reports
+  --explain                Say on each report card which grade band the average fell in
+  --filter-grade <list>    Only print and export students with these grades, e.g. C,D
+  --min-grade <A-D>        Raise any grade below this one to it, noted on the report card
+  --max-grade <A-D>        Lower any grade above this one to it, noted on the report card
+  --output <file>          Write the results in the format named by the extension
+                           (.csv, .json, .md, .html, .xml, or .pdf with the `pdf` feature)
+  --export-csv <file>      Write the results as CSV (with --with-rank, --append)
+  --lang en|es             Language of the CSV column headers
+  --line-ending lf|crlf    Line endings in exported text files (default: the platform's)
+  --with-summary           End Markdown and HTML exports with the class summary
+  --anonymize              Replace names with \"Student 001\" etc. in exports
+  --xml <file>             Write the results as XML
+  --pdf <file>             Write printable report cards (needs the `pdf` feature)
+  --list-formats           List the supported export formats
+  --force-color            Keep colors when output is redirected (overrides NO_COLOR)
+  --clear                  Clear the terminal before each student is entered
+  --commands               Type commands (add, list, show, stats, export, discard all, quit) instead of the menu
+  --quick-add              Start by typing students one per line, e.g. John Smith;315;4
+  --quick-delimiter <char> The field separator for --quick-add (default ;)
+  --error-format human|json
+  --non-interactive        Fail with exit status 3 instead of waiting for typed input
+  --generate <count>       Fabricate a class of students (needs the `testutil` feature)
+  --seed <n>               Which class --generate produces";
+
+/// Students read from a supporting file, with the scale they are graded on.
+struct ClassFile {
+    students: Vec<Student>,
+    scale: GradingScale, // The file's own `# scale:` comment, or else the scale in use
+}
+
+/// Reads the students from a supporting .json or .csv file, such as the
+/// re-sits for --merge or last term's class for --previous. A CSV's `# scale:`
+/// comment grades that file's students only; it never changes the scale the
+/// rest of the run uses. Exits on error.
+fn load_class_file(path: &str, options: &Options) -> ClassFile {
+    match format_for_path(path) {
+        Ok(ExportFormat::Json) => match load_json_file(path, options.strict, options.duplicate_subjects) {
+            Ok(students) => ClassFile { students, scale: grading_scale() },
+            Err(err) => {
+                report_error(options.error_format, &err);
+                process::exit(1);
+            }
+        },
+        _ => match load_csv_file(path, false) {
+            Ok(import) => ClassFile { students: import.students, scale: import.scale.unwrap_or_else(grading_scale) },
+            Err(err) => {
+                report_error(options.error_format, &err);
+                process::exit(1);
+            }
+        },
+    }
+}
+
+/// Merges each re-sit record into the student with the same ID. Records that
+/// match nobody, or can't be merged, are reported on stderr and left out.
+fn merge_resits(students: &mut [Student], resits: &[Student]) {
+    for resit in resits {
+        let target = students
+            .iter_mut()
+            .find(|student| !resit.student_id.is_empty() && student.student_id == resit.student_id);
+        let result = match target {
+            Some(student) => student.merge(resit),
+            None => {
+                eprintln!("Warning: no student with ID '{}' to merge {}'s re-sit into", resit.student_id, resit.name);
+                continue;
+            }
+        };
+        if let Err(err) = result {
+            eprintln!("Warning: could not merge {}'s re-sit: {}", resit.name, err);
+        }
+    }
+}
+
+/// Opens and reads every student from the CSV file at the given path.
+fn load_csv_file(path: &str, continue_on_error: bool) -> Result<CsvImport, CsvError> {
+    let file = File::open(path)?;
+    read_students_from_csv(BufReader::new(file), continue_on_error)
+}
+
+/// ---
+/// ### 19. Main Application Logic
+/// This is the entry point of the program. Execution begins here.
+/// It orchestrates the flow: welcoming the user, getting student details,
+/// creating a Student object, and then printing the report card.
+/// ---
+fn main() {
+    let options = match parse_args(env::args().skip(1)) {
+        Ok(options) => options,
+        Err(message) => {
+            eprintln!("Error: {}", message);
+            eprintln!("{}", USAGE);
+            process::exit(2);
+        }
+    };
+
+    FORCE_COLOR.store(options.force_color, Ordering::Relaxed);
+    if options.non_interactive {
+        let _ = NON_INTERACTIVE.set(options.error_format);
+    }
+    CLEAR_SCREEN.store(options.clear, Ordering::Relaxed);
+    if options.min_grade.is_some() || options.max_grade.is_some() {
+        let _ = GRADE_BOUNDS.set((options.min_grade.unwrap_or(Grade::D), options.max_grade.unwrap_or(Grade::A)));
+    }
+    IGNORE_ZEROS.store(options.ignore_zeros, Ordering::Relaxed);
+
+    if options.list_formats {
+        for format in ExportFormat::all() {
+            println!("{:<5} .{}", format.name(), format.extension());
+        }
+        return;
+    }
+
+    let mut config = match &options.config_path {
+        Some(path) => match load_config_file(path) {
+            Ok(config) => config,
+            Err(err) => {
+                report_error(options.error_format, &err);
+                process::exit(1);
+            }
+        },
+        None => Config::default(),
+    };
+    if let Some(label) = config.no_grade_label.clone() {
+        let _ = NO_GRADE_LABEL.set(label); // Only ever set here, before any grade is shown
+    }
+    if let Some(precision) = options.precision {
+        config.format.precision = precision; // The command line wins over the config file
+    }
+    config.format.grades_only = options.grades_only;
+    config.format.trim_zeros = options.trim_zeros;
+    config.explain = options.explain;
+    config.benchmark = options.benchmark;
+    if !options.pass_marks.is_empty() {
+        // Subject pass marks turn the check on, with the usual mark for the rest
+        config.subject_pass_mark = config.subject_pass_mark.or(Some(DEFAULT_SUBJECT_PASS_MARK));
+        config.subject_pass_marks = options.pass_marks.clone();
+    }
+    config.top = options.top;
+    config.bottom = options.bottom;
+    config.no_summary = options.no_summary;
+    config.commands = options.commands;
+    config.quick_add = options.quick_add.then(|| options.quick_delimiter.unwrap_or(DEFAULT_QUICK_DELIMITER));
+    config.duplicate_subjects = options.duplicate_subjects;
+    config.line_ending = options.line_ending;
+    config.compare_scale = options.compare_scale;
+    config.subject_names = options.subject_names.clone();
+    if let Some(path) = &options.curriculum_path {
+        // The subject list uses the same one-per-line format as a roster
+        match File::open(path).and_then(|file| read_roster(BufReader::new(file))) {
+            Ok(subjects) => config.curriculum = Some(subjects),
+            Err(source) => {
+                report_error(options.error_format, &FileError { what: "read curriculum", source });
+                process::exit(1);
+            }
+        }
+    }
+
+    if !options.transcript_paths.is_empty() {
+        // Transcript mode: print every student's record across the terms and stop
+        let terms: Vec<ClassFile> =
+            options.transcript_paths.iter().map(|path| load_class_file(path, &options)).collect();
+        let without_id =
+            terms.iter().flat_map(|term| &term.students).filter(|student| student.student_id.is_empty()).count();
+        if without_id > 0 {
+            let noun = if without_id == 1 { "record" } else { "records" };
+            eprintln!("Warning: {} {} without a student ID left out of the transcripts", without_id, noun);
+        }
+        let mut stdout = io::stdout().lock();
+        for (student_id, transcript) in build_transcript(&terms) {
+            if let Err(err) = write_transcript(&mut stdout, &student_id, &transcript, &config.format) {
+                stdout_failed(options.error_format, "write transcripts", err);
+            }
+        }
+        return;
+    }
+
+    #[cfg(feature = "testutil")]
+    let generated = options.generate.map(|count| generate_students(count, options.seed));
+    #[cfg(not(feature = "testutil"))]
+    let generated: Option<Vec<Student>> = None;
+
+    let mut skipped_rows = Vec::new();
+    let batch_students = if generated.is_some() {
+        generated
+    } else if let Some(path) = &options.csv_path {
+        match load_csv_file(path, options.continue_on_error) {
+            Ok(import) => {
+                skipped_rows = import.skipped;
+                if let Some(scale) = import.scale {
+                    let _ = GRADING_SCALE.set(scale); // Set once, before anyone is graded
+                }
+                Some(import.students)
+            }
+            Err(err) => {
+                report_error(options.error_format, &err);
+                process::exit(1);
+            }
+        }
+    } else if let Some(path) = &options.json_path {
+        match load_json_file(path, options.strict, options.duplicate_subjects) {
+            Ok(students) => Some(students),
+            Err(err) => {
+                report_error(options.error_format, &err);
+                process::exit(1);
+            }
+        }
+    } else {
+        None
+    };
+    let batch_students = match (batch_students, &options.merge_path) {
+        (Some(mut students), Some(path)) => {
+            let resits = load_class_file(path, &options).students; // Graded with the class they join
+            merge_resits(&mut students, &resits);
+            Some(students)
+        }
+        (students, _) => students,
+    };
+    let batch = batch_students.is_some() || options.roster_path.is_some();
+    let interactive = batch_students.is_none(); // Someone is at the keyboard to answer prompts
+
+    let students = if let Some(students) = batch_students {
+        // Batch mode: print a report card for every student in the file or generated class
+        // Loaded after this class, so a previous file without a scale of its own is graded on this one's
+        config.previous = options.previous_path.as_deref().map(|path| load_class_file(path, &options));
+        let mut stdout = io::stdout().lock();
+        if options.legend {
+            // After loading, so it shows the file's own scale
+            if let Err(err) = writeln!(stdout, "{}", format_legend(&config)) {
+                stdout_failed(options.error_format, "write legend", err);
+            }
+        }
+        // Each group of cards is printed under its rank's heading when announcing
+        let groups: Vec<(Option<usize>, Vec<&Student>)> = if options.summary_only || options.student.is_some() {
+            Vec::new()
+        } else if options.announce {
+            let groups = announcement_order(&students, &options.grade_filter);
+            groups.into_iter().map(|(rank, group)| (Some(rank), group)).collect()
+        } else {
+            vec![(None, filter_by_grade(&students, &options.grade_filter))]
+        };
+        for (rank, group) in groups {
+            let mut written = match rank {
+                Some(rank) => writeln!(stdout, "\n{}", announcement_heading(rank, group.len())),
+                None => Ok(()),
+            };
+            for student in group {
+                written = written.and_then(|()| match options.report_style {
+                    ReportStyle::Card => student.write_report_card(&mut stdout, &config),
+                    ReportStyle::Compact => writeln!(stdout, "{}", student.format_compact(&config.format)),
+                });
+            }
+            if let Err(err) = written {
+                stdout_failed(options.error_format, "write report cards", err);
+            }
+        }
+        if let Some(key) = &options.student {
+            let Some(student) = find_student(&students, key) else {
+                report_error(options.error_format, &StudentNotFound(key.clone()));
+                process::exit(1);
+            };
+            let written = match options.report_style {
+                ReportStyle::Card => stdout.write_all(student.report_string(&config).as_bytes()),
+                ReportStyle::Compact => writeln!(stdout, "{}", student.format_compact(&config.format)),
+            };
+            if let Err(err) = written {
+                stdout_failed(options.error_format, "write report card", err);
+            }
+        } else if !config.no_summary {
+            let written = write_summary(&mut stdout, &students, &config, colors_enabled());
+            if let Err(err) = written {
+                stdout_failed(options.error_format, "write summary", err);
+            }
+        }
+        drop(stdout); // Release the lock before any later prompt or notice
+        if !skipped_rows.is_empty() {
+            // Listed last so they aren't lost above the report cards
+            let noun = if skipped_rows.len() == 1 { "line" } else { "lines" };
+            eprintln!("Skipped {} invalid CSV {}:", skipped_rows.len(), noun);
+            for err in &skipped_rows {
+                report_error(options.error_format, err);
+            }
+        }
+        students
+    } else {
+        // Read the roster up front so a bad path fails before any prompting
+        let roster = match &options.roster_path {
+            Some(path) => match File::open(path).and_then(|file| read_roster(BufReader::new(file))) {
+                Ok(names) => Some(names),
+                Err(source) => {
+                    report_error(options.error_format, &FileError { what: "read roster", source });
+                    process::exit(1);
+                }
+            },
+            None => None,
+        };
+        #[cfg(feature = "ctrlc")]
+        install_interrupt_handler();
+        if options.custom_scale {
+            let _ = GRADING_SCALE.set(get_custom_scale()); // Set once, before anyone is graded
+        }
+        // After the custom scale, so a previous file without a scale of its own is graded on it
+        config.previous = options.previous_path.as_deref().map(|path| load_class_file(path, &options));
+        if options.legend {
+            say(&format_legend(&config));
+        }
+        run_interactive(roster, &config, options.entry_mode)
+    };
+
+    // Numbering follows the whole class, so a filtered export keeps the same pseudonyms
+    let anonymized = options.anonymize.then(|| anonymize(&students));
+    let exported = anonymized.as_deref().unwrap_or(&students);
+    let selected = filter_by_grade(exported, &options.grade_filter);
+    let summary = options.with_summary.then(|| summary_text(exported, &config));
+    for (format, path) in &options.exports {
+        warn_on_extension_mismatch(*format, path);
+        let notice = export_notice(*format, path, selected.len());
+        if interactive {
+            // Give the user a chance to back out of a file they didn't expect
+            if !get_yes_no(&format!("{}. Proceed?", notice), Some(false)) {
+                println!("Skipped {}.", path);
+                continue;
+            }
+        } else {
+            eprintln!("{}", notice); // On stderr, like the run summary, to keep stdout clean
+        }
+        if let Err(source) = write_export(*format, path, &selected, options.csv_export, summary.as_deref(), &config) {
+            report_error(options.error_format, &FileError { what: format.write_action(), source });
+            process::exit(1);
+        }
+    }
+
+    // After a batch run or export, recap on stderr so it survives stdout redirection
+    if batch || !options.exports.is_empty() {
+        eprintln!("{}", format_run_summary(&class_summary(&students)));
+    }
+    if interactive {
+        remove_autosave(); // The session ended properly, so there is nothing to recover
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn csv_row_missing_num_subjects_is_wrong_column_count() {
+        let csv = "name,total_marks,num_subjects\nAsha,240\n";
+        match read_students_from_csv(csv.as_bytes(), false) {
+            Err(CsvError::WrongColumnCount { line, expected, found }) => {
+                assert_eq!((line, expected, found), (2, 3, 2));
+            }
+            Err(err) => panic!("expected WrongColumnCount, got {}", err),
+            Ok(_) => panic!("expected WrongColumnCount, got a successful import"),
+        }
+    }
+
+    #[test]
+    fn parse_u32_accepts_thousands_separators() {
+        assert_eq!(parse_u32("1,200"), Some(1200));
+        assert_eq!(parse_u32("1200"), Some(1200));
+        assert_eq!(parse_u32("12,34"), None);
+    }
+
+    #[test]
+    fn grade_predicates_cover_every_variant() {
+        // (grade, passing, distinction, failing)
+        let expected = [
+            (Grade::A, true, true, false),
+            (Grade::B, true, false, false),
+            (Grade::C, true, false, false),
+            (Grade::D, false, false, true),
+            (Grade::Incomplete, false, false, false),
+            (Grade::Invalid, false, false, false),
+        ];
+        for (grade, passing, distinction, failing) in expected {
+            assert_eq!(grade.is_passing(), passing, "{:?}.is_passing()", grade);
+            assert_eq!(grade.is_distinction(), distinction, "{:?}.is_distinction()", grade);
+            assert_eq!(grade.is_failing(), failing, "{:?}.is_failing()", grade);
+        }
+    }
+
+    #[test]
+    fn csv_quoted_field_can_span_lines() {
+        let csv = "name,total_marks,num_subjects\n\"Asha\nRao\",240,3\nBen,180,2\n";
+        let import = read_students_from_csv(csv.as_bytes(), false).expect("valid CSV");
+        let names: Vec<&str> = import.students.iter().map(|student| student.name.as_str()).collect();
+        assert_eq!(names, ["Asha\nRao", "Ben"]);
+        assert_eq!(import.students[0].total_marks, 240);
+    }
+
+    #[test]
+    fn csv_quoted_header_column_keeps_layout() {
+        // Spreadsheets often quote every header column
+        let csv = "\"student_id\",\"name, full\",\"total_marks\",\"num_subjects\",\"section\"\nS1,Asha,240,3,B\n";
+        let import = read_students_from_csv(csv.as_bytes(), false).expect("valid CSV");
+        let student = &import.students[0];
+        assert_eq!(student.student_id, "S1");
+        assert_eq!(student.section.as_deref(), Some("B"));
+    }
+
+    #[test]
+    fn display_name_truncates_long_names() {
+        let format = ReportFormat { max_name_length: 8, ..ReportFormat::default() };
+        assert_eq!(format.display_name("Asha Rao"), "Asha Rao");
+        assert_eq!(format.display_name("Asha Ramakrishnan"), "Asha Ra…");
+        assert_eq!(format.display_name("Asha Ramakrishnan").chars().count(), 8);
+        let student = Student::new("Asha Ramakrishnan".to_string(), 240, 3);
+        assert_eq!(student.format_compact(&format), "Asha Ra… | 240/3 | 80.00 | B");
+    }
+
+    fn subject(name: &str, mark: u32) -> Subject {
+        Subject { name: name.to_string(), mark: Mark::Score(mark), components: Vec::new() }
+    }
+
+    #[test]
+    fn exports_include_rounded_percentage() {
+        let student = Student::with_subjects("Asha".to_string(), vec![subject("Math", 67), subject("Lab", 20)]);
+        let max_marks = BTreeMap::from([("lab".to_string(), 30)]);
+        let format = ReportFormat::default();
+        // 87 marks out of a possible 130 is 66.923…%
+        assert_eq!(export_percentage(&student, &max_marks, &format).as_deref(), Some("66.92"));
+
+        let mut csv = Vec::new();
+        write_csv(&[&student], &mut csv, false, true, Language::default(), &max_marks, &format).unwrap();
+        let csv = String::from_utf8(csv).unwrap();
+        let mut lines = csv.lines();
+        let header: Vec<&str> = lines.next().unwrap().split(',').collect();
+        let row: Vec<&str> = lines.next().unwrap().split(',').collect();
+        let column = header.iter().position(|&column| column == "percentage").expect("percentage column");
+        assert_eq!(row[column], "66.92");
+
+        let json = to_json(&[&student], &max_marks, &format);
+        assert!(json.contains("\"percentage\":66.92,"), "{}", json);
+    }
+
+    #[test]
+    fn spanish_csv_headers() {
+        let spanish = Language::parse("es").expect("es is supported");
+        assert_eq!(
+            csv_export_header(false, false, spanish),
+            "id_estudiante,nombre,total_puntos,num_asignaturas,promedio,porcentaje,calificacion,calificacion_calculada"
+        );
+        assert_eq!(csv_export_header(false, true, spanish), "id_estudiante,nombre,calificacion");
+        assert!(csv_export_header(true, false, spanish).ends_with(",puesto,percentil"));
+    }
+
+    #[test]
+    fn grade_index_round_trips() {
+        for grade in [Grade::A, Grade::B, Grade::C, Grade::D, Grade::Invalid, Grade::Incomplete] {
+            assert_eq!(Grade::from_index(grade.index()), Some(grade));
+        }
+        for index in 0..GRADE_SLOTS {
+            assert_eq!(Grade::from_index(index).map(|grade| grade.index()), Some(index));
+        }
+        assert_eq!(Grade::from_index(6), None);
+    }
+
+    #[test]
+    fn generated_class_is_stable_and_spans_every_band() {
+        let class = generate_students(200, 42);
+        let again = generate_students(200, 42);
+        let names = |students: &[Student]| students.iter().map(|student| student.name.clone()).collect::<Vec<_>>();
+        assert_eq!(names(&class), names(&again));
+        assert_eq!(class.len(), 200);
+        assert!(class.iter().all(|student| student.scores().all(|mark| mark <= MAX_SUBJECT_MARK)));
+
+        let summary = class_summary(&class);
+        for grade in [Grade::A, Grade::B, Grade::C, Grade::D] {
+            assert!(summary.grade_counts[grade.index()] > 0, "no student graded {:?}", grade);
+        }
+    }
+
+    #[test]
+    fn subject_everyone_missed_has_no_class_average() {
+        let absent = |name: &str| Subject { name: name.to_string(), mark: Mark::Absent, components: Vec::new() };
+        let class = vec![
+            Student::with_subjects("Asha".to_string(), vec![subject("Math", 80), absent("Art")]),
+            Student::with_subjects("Ben".to_string(), vec![subject("Math", 60), absent("Art")]),
+        ];
+        let averages = subject_class_averages(&class);
+        assert_eq!(averages.get("Math"), Some(&70.0));
+        assert!(!averages.contains_key("Art"));
+        assert_eq!(weakest_subjects(&class, 3), [("Math".to_string(), 70.0)]);
+    }
+
+    /// A writer whose reader has gone away, like stdout piped into `head`.
+    struct ClosedPipe;
+
+    impl Write for ClosedPipe {
+        fn write(&mut self, _: &[u8]) -> io::Result<usize> {
+            Err(io::Error::from(io::ErrorKind::BrokenPipe))
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Err(io::Error::from(io::ErrorKind::BrokenPipe))
+        }
+    }
+
+    #[test]
+    fn end_of_input_and_closed_output_are_errors() {
+        assert_eq!(read_answer(&mut "  42 \n".as_bytes()).unwrap(), "42");
+        assert_eq!(read_answer(&mut "\n".as_bytes()).unwrap(), "");
+        let eof = read_answer(&mut "".as_bytes()).unwrap_err();
+        assert_eq!(eof.kind(), io::ErrorKind::UnexpectedEof);
+        let closed = write_prompt(&mut ClosedPipe, "Enter choice: ").unwrap_err();
+        assert_eq!(closed.kind(), io::ErrorKind::BrokenPipe);
+    }
+
+    #[test]
+    fn json_round_trip_keeps_every_field() {
+        let weights = Weights { internal: 0.4, exam: 0.6 };
+        let mut weighted = Student::with_components("Asha".to_string(), 150, 240, 3, weights);
+        weighted.student_id = "S1".to_string();
+        weighted.section = Some("7B".to_string());
+        let mut detailed = Student::with_subjects(
+            "Ben".to_string(),
+            vec![subject("Math", 72), Subject { name: "Art".to_string(), mark: Mark::Absent, components: Vec::new() }],
+        );
+        detailed.student_id = "S2".to_string();
+        detailed.override_grade = Some(Grade::A);
+
+        let json = to_json(&[&weighted, &detailed], &BTreeMap::new(), &ReportFormat::default());
+        let read = read_students_from_json(&json, true, DuplicateSubjects::default()).expect("own export");
+        assert_eq!(read.len(), 2);
+        assert_eq!(read[0].section.as_deref(), Some("7B"));
+        assert_eq!((read[0].internal, read[0].exam), (150, 240));
+        assert_eq!(read[0].calculate_average(), weighted.calculate_average());
+        assert_eq!(read[1].section, None);
+        assert_eq!(read[1].override_grade, Some(Grade::A));
+        assert_eq!(read[1].subjects[1].mark, Mark::Absent);
+    }
+
+    #[test]
+    fn parse_percent_accepts_0_to_100_with_optional_sign() {
+        assert_eq!(parse_percent("72.5"), Some(72.5));
+        assert_eq!(parse_percent(" 72.5 % "), Some(72.5));
+        assert_eq!(parse_percent("100%"), Some(100.0));
+        assert_eq!(parse_percent("100.1"), None);
+        assert_eq!(parse_percent("-1"), None);
+        assert_eq!(parse_percent("abc"), None);
+    }
+
+    #[test]
+    fn class_keeps_tally_in_step_and_snapshots_are_independent() {
+        let mut class = Class::from_students(vec![Student::new("Asha".to_string(), 240, 3)]);
+        class.add(Student::new("Ben".to_string(), 120, 2));
+        let format = ReportFormat::default();
+        assert_eq!(class.progress_line(&format), "Entered 2 students so far, running class average 70.00.");
+
+        let snapshot = class.snapshot();
+        class.edit(|students| students[1].total_marks = 180);
+        assert_eq!(class.progress_line(&format), "Entered 2 students so far, running class average 85.00.");
+        assert_eq!(snapshot[1].total_marks, 120);
+
+        class.clear();
+        assert!(class.students().is_empty());
+        assert_eq!(snapshot.len(), 2);
+    }
+
+    #[test]
+    fn transcript_gpa_follows_report_precision() {
+        let transcript = Transcript {
+            name: "Asha".to_string(),
+            terms: vec![
+                Some(TermResult { average: Some(80.0), grade: Grade::A }),
+                None,
+                Some(TermResult { average: Some(70.0), grade: Grade::B }),
+            ],
+        };
+        let format = ReportFormat { precision: 1, ..ReportFormat::default() };
+        let mut out = Vec::new();
+        write_transcript(&mut out, "S1", &transcript, &format).unwrap();
+        let text = String::from_utf8(out).unwrap();
+        assert!(text.contains("Term 2         : not enrolled\n"));
+        assert!(text.contains("Cumulative GPA : 3.5\n"));
+    }
+
+    #[test]
+    fn class_gpa_follows_report_precision() {
+        let format = ReportFormat { trim_zeros: true, ..ReportFormat::default() };
+        let mut out = Vec::new();
+        write_class_gpa(&mut out, &ClassGpa { gpa: Some(3.0), excluded: 1 }, &format).unwrap();
+        assert_eq!(String::from_utf8(out).unwrap(), "Class GPA      : 3 (1 student without credits excluded)\n");
+    }
+
+    #[test]
+    fn crlf_line_endings_are_not_doubled() {
+        let mut writer = LineEndingWriter::new(Vec::new(), LineEnding::CrLf);
+        writer.write_all(b"a\nb\r\nc\r").unwrap();
+        writer.write_all(b"\nd\n").unwrap();
+        assert_eq!(writer.inner, b"a\r\nb\r\nc\r\nd\r\n");
+        assert_eq!(LineEnding::CrLf.apply("a\nb\r\n"), "a\r\nb\r\n");
+        assert_eq!(LineEnding::Lf.apply("a\nb\r\n"), "a\nb\r\n");
+    }
+
+    #[test]
+    fn huge_component_marks_do_not_overflow_the_total() {
+        let weights = Weights { internal: 0.3, exam: 0.7 };
+        let student = Student::with_components("Asha".to_string(), 4_000_000_000, 4_000_000_000, 3, weights);
+        assert_eq!(student.total_marks, u32::MAX);
+    }
+
+    #[test]
+    fn marks_needed_uses_the_weighted_total() {
+        let weights = Weights { internal: 0.3, exam: 0.7 };
+        let student = Student::with_components("Asha".to_string(), 250, 200, 3, weights);
+        let scale = GradingScale::default();
+        assert_eq!(Student::marks_needed_for(Grade::B, &student, &scale), Some(10));
+        assert_eq!(Student::marks_needed_for(Grade::A, &student, &scale), Some(55));
+    }
+
+    #[test]
+    fn transcript_grades_each_term_on_its_own_scale() {
+        let mut student = Student::new("Ann".to_string(), 240, 4);
+        student.student_id = "S1".to_string();
+        let lenient = ClassFile { students: vec![student.clone()], scale: GradingScale { a: 50.0, b: 40.0, c: 30.0 } };
+        let usual = ClassFile { students: vec![student], scale: GradingScale::default() };
+        let transcripts = build_transcript(&[lenient, usual]);
+        let grades: Vec<Grade> = transcripts["S1"].terms.iter().flatten().map(|term| term.grade).collect();
+        assert_eq!(grades, [Grade::A, Grade::C]);
+    }
+
+    #[test]
+    fn report_string_is_the_report_card() {
+        let student = Student::new("Asha".to_string(), 240, 3);
+        let config = Config::default();
+        let mut card = Vec::new();
+        student.write_report_card(&mut card, &config).unwrap();
+        let text = student.report_string(&config);
+        assert_eq!(text.as_bytes(), card);
+        assert!(text.contains("Name           : Asha\n"));
+    }
+
+    #[test]
+    fn overall_average_uses_weighted_totals() {
+        let weights = Weights { internal: 0.3, exam: 0.7 };
+        let class = [Student::with_components("Asha".to_string(), 250, 200, 3, weights)];
+        let summary = class_summary(&class);
+        assert_eq!(summary.grand_total, 450);
+        assert!((summary.overall_average - 215.0 / 3.0).abs() < 1e-9);
+    }
 }